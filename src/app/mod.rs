@@ -1,19 +1,29 @@
 use lib::ImguiSdl2;
 
 use sdl2::image::{InitFlag};
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::pixels::Color;
 use sdl2::rect::Point;
+use sdl2::video::WindowContext;
 
+use atlas;
 use config::Config;
+use definition;
+use definition::{Animation, AnimationDefinition, DefinitionError, Grid, SpritesheetDefinition};
+use export;
+use file_utils;
 use fragment::{Doodad, Fragment, Spritesheet};
+use history::{Edit, History};
+use lint;
 use mymath::{check_rect, rotate_rectangle};
 use resource_manager::ResourceManager;
+use scene;
+use ui_stuff::timer::Timer;
 use ui_stuff::{
-    MainInterface, MainInterfaceCommand, MainMenuCommand, MainMenuInterface, UserInterface,
+    DoodadRow, MainInterface, MainInterfaceCommand, MainMenuCommand, MainMenuInterface,
+    UserInterface,
 };
 
-use file_utils;
-
 pub struct App {
     exit: bool,
     config: Config,
@@ -25,7 +35,25 @@ pub struct App {
 
     holding_button: bool,
     holding_index: bool,
+    holding_pivot: bool,
+    //frame of the motion path point being dragged
+    holding_path: Option<usize>,
     frame: i32,
+    //doodad the settings and handles are for, clicking no longer changes the drawing order
+    selected: usize,
+
+    //animations that are not in the scene, the one in the scene is only up to date after capture
+    definition: AnimationDefinition,
+    animation: usize,
+
+    history: History,
+    drag_start: Option<AnimationDefinition>,
+
+    dirty: bool,
+    autosave_timer: Timer,
+    autosave_interval: u64,
+
+    default_duration: u32,
 }
 
 impl App {
@@ -49,9 +77,20 @@ impl App {
             gl_attr.set_context_version(3, 1);
         }
 
+        let mut config = Config::create("./usr/config.csv");
+        let history_limit = config.read("history_limit").parse::<usize>().unwrap_or(100);
+        let autosave_interval = config
+            .read("autosave_interval")
+            .parse::<u64>()
+            .unwrap_or(30000);
+        let default_duration = config
+            .read("starting_frametime")
+            .parse::<u32>()
+            .unwrap_or(100);
+
         App {
             exit: false,
-            config: Config::create("./usr/config.csv"),
+            config: config,
             sdl_ctx: sdl_context,
             video: video,
 
@@ -60,13 +99,38 @@ impl App {
 
             holding_button: false,
             holding_index: false,
+            holding_pivot: false,
+            holding_path: None,
             frame: 0,
+            selected: 0,
+
+            //replaced once the starting spritesheet is loaded
+            definition: AnimationDefinition::new(
+                SpritesheetDefinition {
+                    name: String::new(),
+                    frames: 0,
+                    durations: Vec::new(),
+                    grid: None,
+                },
+                Vec::new(),
+            ),
+            animation: 0,
+
+            history: History::new(history_limit),
+            drag_start: None,
+
+            dirty: false,
+            autosave_timer: Timer::create(),
+            autosave_interval: autosave_interval,
+
+            default_duration: default_duration,
         }
     }
 
     fn draw_rectangle_around_active(
         canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
         points: [Point; 4],
+        pivot: Point,
     ) {
         canvas.set_draw_color(Color::RGB(200, 20, 20));
         canvas.set_scale(1.0, 1.0).unwrap();
@@ -75,11 +139,344 @@ impl App {
         canvas.draw_line(points[1], points[2]).unwrap();
         canvas.draw_line(points[2], points[3]).unwrap();
         canvas.draw_line(points[3], points[0]).unwrap();
+
+        canvas.set_draw_color(Color::RGB(20, 200, 20));
+        canvas.draw_rect(App::pivot_handle(pivot)).unwrap();
+    }
+
+    //whole texture at the spritesheet position with the cells of the grid outlined,
+    //cells past the frame count are greyed out
+    //grids that do not fit are only reported by the dialog, they are not drawn
+    fn draw_slicing_preview(
+        canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+        texture: &sdl2::render::Texture,
+        grid: &Grid,
+        frames: usize,
+        origin: Point,
+    ) {
+        let query = texture.query();
+        if atlas::check_grid(grid, query.width, query.height, frames).is_err() {
+            return;
+        }
+
+        let area = sdl2::rect::Rect::new(origin.x, origin.y, query.width, query.height);
+
+        canvas.set_scale(1.0, 1.0).unwrap();
+        canvas.set_draw_color(Color::RGB(40, 40, 40));
+        canvas.fill_rect(area).unwrap();
+        canvas.copy(texture, None, Some(area)).unwrap();
+
+        for index in 0..(grid.columns * grid.rows) as usize {
+            let (x, y) = match atlas::grid_cell(grid, index) {
+                Some(cell) => cell,
+                None => break,
+            };
+
+            if index < frames {
+                canvas.set_draw_color(Color::RGB(200, 20, 20));
+            } else {
+                canvas.set_draw_color(Color::RGB(120, 120, 120));
+            }
+
+            canvas
+                .draw_rect(sdl2::rect::Rect::new(
+                    origin.x + x,
+                    origin.y + y,
+                    grid.frame_width.max(1),
+                    grid.frame_height.max(1),
+                ))
+                .unwrap();
+        }
+    }
+
+    //thumbnails go below imgui, into the slots the timeline window left for them
+    fn draw_timeline(
+        canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+        spritesheet: &Spritesheet,
+        area: sdl2::rect::Rect,
+        slots: &Vec<sdl2::rect::Rect>,
+    ) {
+        canvas.set_scale(1.0, 1.0).unwrap();
+        canvas.set_clip_rect(Some(area));
+
+        canvas.set_draw_color(Color::RGB(20, 20, 20));
+        canvas.fill_rect(area).unwrap();
+
+        for (frame, slot) in slots.iter().enumerate() {
+            let source = match spritesheet.get_frame_rect(frame) {
+                Some(source) => source,
+                None => break,
+            };
+
+            //fitted into the slot without stretching
+            let scale = (slot.width() as f32 / source.width() as f32)
+                .min(slot.height() as f32 / source.height() as f32);
+            let width = ((source.width() as f32 * scale) as u32).max(1);
+            let height = ((source.height() as f32 * scale) as u32).max(1);
+
+            let target = sdl2::rect::Rect::new(
+                slot.x() + (slot.width() - width) as i32 / 2,
+                slot.y() + (slot.height() - height) as i32 / 2,
+                width,
+                height,
+            );
+
+            canvas
+                .copy(spritesheet.get_texture(), Some(source), Some(target))
+                .unwrap();
+        }
+
+        canvas.set_clip_rect(None);
     }
 
-    fn bring_to_front(vec: &mut Vec<Doodad>, index: usize) {
-        let el = vec.remove(index);
-        vec.insert(0, el);
+    //pivot of the doodad in every frame joined in frame order, the current frame is filled in
+    fn draw_motion_path(
+        canvas: &mut sdl2::render::Canvas<sdl2::video::Window>,
+        path: &Vec<Point>,
+        current: usize,
+    ) {
+        canvas.set_scale(1.0, 1.0).unwrap();
+        canvas.set_draw_color(Color::RGB(230, 200, 40));
+
+        canvas.draw_lines(path.as_slice()).unwrap();
+
+        for (frame, &point) in path.iter().enumerate() {
+            let handle = sdl2::rect::Rect::from_center(point, 5, 5);
+
+            if frame == current {
+                canvas.fill_rect(handle).unwrap();
+            } else {
+                canvas.draw_rect(handle).unwrap();
+            }
+        }
+    }
+
+    //path point of a frame other than the current one, the pivot handle covers the current one
+    fn path_handle(path: &Vec<Point>, current: usize, point: Point) -> Option<usize> {
+        path.iter().enumerate().position(|(frame, &handle)| {
+            frame != current && App::pivot_handle(handle).contains_point(point)
+        })
+    }
+
+    fn pivot_handle(pivot: Point) -> sdl2::rect::Rect {
+        sdl2::rect::Rect::from_center(pivot, 9, 9)
+    }
+
+    //undo and redo keep the view position, frame is kept unless the frame count changed
+    fn restore<'l>(
+        &mut self,
+        manager: &mut ResourceManager<'l, WindowContext>,
+        definition: &AnimationDefinition,
+        spritesheet: &mut Spritesheet<'l>,
+        doodads: &mut Vec<Doodad<'l>>,
+    ) {
+        let position = spritesheet.real_position();
+        let index = self.animation.min(definition.animations.len() - 1);
+        let animation = definition.animation(index);
+
+        match scene::build(manager, &animation, position.x, position.y) {
+            Ok((new_spritesheet, new_doodads)) => {
+                if index != self.animation
+                    || new_spritesheet.get_frames_amount() != spritesheet.get_frames_amount()
+                {
+                    self.frame = 0;
+                    self.main_ui
+                        .reset(new_spritesheet.get_frames_amount() as i32);
+                }
+                self.main_ui
+                    .set_durations(animation.spritesheet.durations.clone());
+                self.set_definition(definition.clone(), index);
+
+                *spritesheet = new_spritesheet;
+                *doodads = new_doodads;
+                self.selected = self.selected.min(doodads.len().saturating_sub(1));
+                self.dirty = true;
+
+                spritesheet.set_frame(self.frame as usize);
+                for doodad in doodads.iter_mut() {
+                    doodad.set_frame(self.frame as usize);
+                }
+
+                match doodads.get(self.selected) {
+                    Some(selected_doodad) => {
+                        self.main_ui.change_settings(
+                            selected_doodad.get_scale(),
+                            selected_doodad.get_rotation() as f32,
+                            selected_doodad.get_flip(),
+                            selected_doodad.get_pivot(),
+                            selected_doodad.get_color(),
+                        );
+                    }
+                    None => {}
+                };
+            }
+            Err(message) => self.main_menu_ui.show_error(message),
+        }
+    }
+
+    //replaces the scene only when the definition and all of its textures could be loaded
+    fn open_definition<'l>(
+        &mut self,
+        manager: &mut ResourceManager<'l, WindowContext>,
+        loaded: Result<AnimationDefinition, DefinitionError>,
+        x_pos: i32,
+        y_pos: i32,
+        spritesheet: &mut Spritesheet<'l>,
+        doodads: &mut Vec<Doodad<'l>>,
+    ) -> bool {
+        let opened = loaded
+            .map_err(|err| err.to_string())
+            .and_then(|definition| {
+                self.open_animation(manager, definition, 0, x_pos, y_pos, spritesheet, doodads)
+            });
+
+        match opened {
+            Ok(()) => true,
+            Err(message) => {
+                self.main_menu_ui.show_error(message);
+                false
+            }
+        }
+    }
+
+    //puts an animation of definition in the scene from its first frame
+    fn open_animation<'l>(
+        &mut self,
+        manager: &mut ResourceManager<'l, WindowContext>,
+        definition: AnimationDefinition,
+        index: usize,
+        x_pos: i32,
+        y_pos: i32,
+        spritesheet: &mut Spritesheet<'l>,
+        doodads: &mut Vec<Doodad<'l>>,
+    ) -> Result<(), String> {
+        let animation = definition.animation(index);
+        let (new_spritesheet, new_doodads) = scene::build(manager, &animation, x_pos, y_pos)?;
+
+        self.frame = 0;
+        self.main_ui
+            .reset(new_spritesheet.get_frames_amount() as i32);
+        self.main_ui
+            .set_durations(animation.spritesheet.durations.clone());
+        self.set_definition(definition, index);
+
+        *spritesheet = new_spritesheet;
+        *doodads = new_doodads;
+        self.selected = 0;
+
+        match doodads.get(self.selected) {
+            Some(selected_doodad) => {
+                self.main_ui.change_settings(
+                    selected_doodad.get_scale(),
+                    selected_doodad.get_rotation() as f32,
+                    selected_doodad.get_flip(),
+                    selected_doodad.get_pivot(),
+                    selected_doodad.get_color(),
+                );
+            }
+            None => {}
+        };
+
+        Ok(())
+    }
+
+    fn set_definition(&mut self, definition: AnimationDefinition, index: usize) {
+        self.main_ui.set_animations(
+            definition
+                .animations
+                .iter()
+                .map(|clip| clip.name.clone())
+                .collect(),
+            index,
+        );
+
+        self.definition = definition;
+        self.animation = index;
+    }
+
+    fn current(&self, spritesheet: &Spritesheet, doodads: &Vec<Doodad>) -> Animation {
+        scene::capture(
+            &self.definition.animations[self.animation].name,
+            spritesheet,
+            doodads,
+            self.main_ui.get_durations(),
+        )
+    }
+
+    //frames of a new spritesheet without a duration in their atlas keep the pace of the
+    //animation being edited
+    fn fallback_duration(&self) -> u32 {
+        match self.main_ui.get_durations().first() {
+            Some(&duration) => duration,
+            None => self.default_duration,
+        }
+    }
+
+    //whole definition with the animation in the scene put back in
+    fn capture(&self, spritesheet: &Spritesheet, doodads: &Vec<Doodad>) -> AnimationDefinition {
+        let mut definition = self.definition.clone();
+        definition.set_animation(self.animation, self.current(spritesheet, doodads));
+
+        definition
+    }
+
+    //loaded definitions can still be off in ways that only show while the animation plays
+    fn report_findings(&mut self, name: &str) {
+        let findings = match file_utils::find_template(name) {
+            Some(path) => lint::lint_file(&path),
+            None => return,
+        };
+
+        match findings {
+            Ok(ref findings) if findings.len() != 0 => {
+                let kind = if lint::has_errors(findings) {
+                    "errors"
+                } else {
+                    "warnings"
+                };
+                let mut message = format!("{} was loaded with {}:", name, kind);
+
+                for finding in findings {
+                    message += &format!("\nline {}", finding);
+                }
+
+                self.main_menu_ui.show_error(message);
+            }
+            _ => {}
+        }
+    }
+
+    fn main_edit(command: &MainInterfaceCommand) -> Option<Edit> {
+        match command {
+            MainInterfaceCommand::Scale(_) => Some(Edit::Scale),
+            MainInterfaceCommand::Rotate(_) => Some(Edit::Rotate),
+            MainInterfaceCommand::Flip(_, _) => Some(Edit::Flip),
+            MainInterfaceCommand::Pivot(_, _) => Some(Edit::Pivot),
+            MainInterfaceCommand::Color(_) => Some(Edit::Color),
+            MainInterfaceCommand::Duration(_, _) => Some(Edit::Duration),
+            MainInterfaceCommand::UniformDuration(_) => Some(Edit::Duration),
+            MainInterfaceCommand::Keyframe(_) => Some(Edit::Keyframe),
+            MainInterfaceCommand::Attach(_) => Some(Edit::Attach),
+            MainInterfaceCommand::Reorder(_) => Some(Edit::Layer),
+            MainInterfaceCommand::FrameLayer(_) => Some(Edit::Layer),
+            MainInterfaceCommand::Hide(_, _) => Some(Edit::Visibility),
+            MainInterfaceCommand::Lock(_, _) => Some(Edit::Lock),
+            _ => None,
+        }
+    }
+
+    fn menu_edit(command: &MainMenuCommand) -> Option<Edit> {
+        match command {
+            MainMenuCommand::New => Some(Edit::New),
+            MainMenuCommand::Load(_) => Some(Edit::Load),
+            MainMenuCommand::Recover => Some(Edit::Recover),
+            MainMenuCommand::AddDoodad(_) => Some(Edit::AddDoodad),
+            MainMenuCommand::ClearDoodads => Some(Edit::ClearDoodads),
+            MainMenuCommand::ChangeSpritesheet(_, _, _) => Some(Edit::ChangeSpritesheet),
+            MainMenuCommand::AddAnimation(_, _, _, _) => Some(Edit::AddAnimation),
+            MainMenuCommand::RemoveAnimation => Some(Edit::RemoveAnimation),
+            _ => None,
+        }
     }
 
     pub fn run(&mut self) {
@@ -148,19 +545,31 @@ impl App {
             .parse::<usize>()
             .unwrap();
         let default_name = self.config.read("starting_filename");
-        let default_filename = default_name.clone() + ".png";
-
-        let mut spritesheet = Spritesheet::new(
-            default_name.clone(),
-            manager.get_spritesheet(&default_filename),
+        let mut spritesheet = scene::spritesheet(
+            &mut manager,
+            &default_name,
+            default_frames,
+            None,
             default_x,
             default_y,
-            default_frames,
-        );
-        self.main_ui.reset(default_frames as i32);
+        )
+        .expect("Could not load the starting spritesheet.");
+        self.main_ui.reset(spritesheet.get_frames_amount() as i32);
+        self.main_ui
+            .set_durations(spritesheet.get_durations(self.default_duration));
 
         let mut doodads: Vec<Doodad> = Vec::new();
 
+        let starting = spritesheet.to_definition(self.main_ui.get_durations().clone());
+        self.set_definition(AnimationDefinition::new(starting, Vec::new()), 0);
+
+        self.main_menu_ui
+            .set_export_background(self.config.read_color("background_color"));
+
+        if file_utils::recovery_path().exists() {
+            self.main_menu_ui.offer_recovery();
+        }
+
         while !self.exit {
             use sdl2::event::Event;
 
@@ -171,26 +580,95 @@ impl App {
                 }
 
                 match event {
-                    Event::Quit { .. } => self.exit = true,
-                    Event::MouseButtonDown { x, y, .. } => {
-                        for i in 0..doodads.len() {
-                            let check = check_rect(
-                                rotate_rectangle(
-                                    doodads[i].real_position(),
-                                    doodads[i].get_rotation() as f32,
-                                    doodads[i].get_scale(),
+                    Event::Quit { .. } => self.main_menu_ui.request(MainMenuCommand::Exit),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Z),
+                        keymod,
+                        ..
+                    } => {
+                        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+                            let definition = if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                                self.history.redo()
+                            } else {
+                                self.history.undo()
+                            };
+
+                            match definition {
+                                Some(definition) => self.restore(
+                                    &mut manager,
+                                    &definition,
+                                    &mut spritesheet,
+                                    &mut doodads,
                                 ),
-                                Point::new(x, y),
-                            );
+                                None => {}
+                            }
+                        }
+                    }
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Left),
+                        ..
+                    } => self.main_ui.step_frame(-1),
+                    Event::KeyDown {
+                        keycode: Some(Keycode::Right),
+                        ..
+                    } => self.main_ui.step_frame(1),
+                    Event::MouseButtonDown { x, y, .. } => {
+                        self.drag_start = Some(self.capture(&spritesheet, &doodads));
+
+                        //locked doodads can still be selected from the panels, just not moved
+                        self.holding_pivot = match doodads.get(self.selected) {
+                            Some(selected_doodad) if !selected_doodad.is_locked() => {
+                                App::pivot_handle(selected_doodad.pivot_position())
+                                    .contains_point(Point::new(x, y))
+                            }
+                            _ => false,
+                        };
+
+                        self.holding_path = match doodads.get(self.selected) {
+                            Some(selected_doodad)
+                                if !self.holding_pivot
+                                    && !selected_doodad.is_locked()
+                                    && self.main_ui.get_motion_path() =>
+                            {
+                                App::path_handle(
+                                    &selected_doodad.path(),
+                                    self.frame as usize,
+                                    Point::new(x, y),
+                                )
+                            }
+                            _ => None,
+                        };
+
+                        if !self.holding_pivot && self.holding_path.is_none() {
+                            //topmost first, hidden and locked doodads let clicks through
+                            for i in scene::draw_order(&doodads).into_iter().rev() {
+                                if doodads[i].is_hidden() || doodads[i].is_locked() {
+                                    continue;
+                                }
 
-                            if check {
-                                self.main_ui.change_settings(
-                                    doodads[i].get_scale(),
-                                    doodads[i].get_rotation() as f32,
+                                let check = check_rect(
+                                    rotate_rectangle(
+                                        doodads[i].real_position(),
+                                        doodads[i].get_rotation() as f32,
+                                        doodads[i].get_scale(),
+                                        doodads[i].get_center(),
+                                        doodads[i].get_flip(),
+                                    ),
+                                    Point::new(x, y),
                                 );
-                                self.holding_index = true; //i as i32; also -> probably will move to bool
-                                App::bring_to_front(&mut doodads, i);
-                                break;
+
+                                if check {
+                                    self.main_ui.change_settings(
+                                        doodads[i].get_scale(),
+                                        doodads[i].get_rotation() as f32,
+                                        doodads[i].get_flip(),
+                                        doodads[i].get_pivot(),
+                                        doodads[i].get_color(),
+                                    );
+                                    self.holding_index = true;
+                                    self.selected = i;
+                                    break;
+                                }
                             }
                         }
 
@@ -198,14 +676,41 @@ impl App {
                     }
 
                     Event::MouseButtonUp { .. } => {
+                        match self.drag_start.take() {
+                            Some(before) => {
+                                let after = self.capture(&spritesheet, &doodads);
+
+                                if self.history.record(Edit::Drag, before, after) {
+                                    self.dirty = true;
+                                }
+                            }
+                            None => {}
+                        }
+
                         self.holding_button = false;
                         self.holding_index = false;
+                        self.holding_pivot = false;
+                        self.holding_path = None;
                     }
 
                     Event::MouseMotion { xrel, yrel, .. } => {
                         if self.holding_button {
-                            if self.holding_index {
-                                doodads.first_mut().unwrap().change_position(xrel, yrel);
+                            if self.holding_pivot {
+                                let selected_doodad = doodads.get_mut(self.selected).unwrap();
+
+                                selected_doodad.change_pivot(xrel, yrel);
+                                self.main_ui.set_pivot(selected_doodad.get_pivot());
+                            } else if let Some(frame) = self.holding_path {
+                                doodads
+                                    .get_mut(self.selected)
+                                    .unwrap()
+                                    .change_frame_position(frame, xrel, yrel);
+                                doodads.get_mut(self.selected).unwrap().retween(frame);
+                            } else if self.holding_index {
+                                let selected_doodad = doodads.get_mut(self.selected).unwrap();
+
+                                selected_doodad.change_position(xrel, yrel);
+                                selected_doodad.retween(self.frame as usize);
                             } else {
                                 spritesheet.change_position(xrel, yrel);
                                 for doodad in &mut doodads {
@@ -220,23 +725,139 @@ impl App {
 
             self.main_ui.update();
 
-            match self.main_ui.check() {
+            //sliders keep sending commands while held, those merge into one history entry
+            if !imgui.is_mouse_down(imgui::ImMouseButton::Left) {
+                self.history.close();
+            }
+
+            let command = self.main_ui.check();
+            let edit = App::main_edit(&command);
+            let before = match edit {
+                Some(_) => Some(self.capture(&spritesheet, &doodads)),
+                None => None,
+            };
+
+            //the panel hides these for locked doodads, a stale command is dropped here
+            match command {
                 MainInterfaceCommand::Scale(scale) => {
-                    match doodads.first_mut() {
-                        Some(first_doodad) => {
-                            first_doodad.set_scale(scale);
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_scale(scale);
+                            selected_doodad.retween(self.frame as usize);
                         }
-                        None => {}
+                        _ => {}
                     };
                 }
                 MainInterfaceCommand::Rotate(angle) => {
-                    match doodads.first_mut() {
-                        Some(first_doodad) => {
-                            first_doodad.set_rotation(angle.into());
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_rotation(angle.into());
+                            selected_doodad.retween(self.frame as usize);
                         }
-                        None => {}
+                        _ => {}
+                    };
+                }
+                MainInterfaceCommand::Flip(horizontal, vertical) => {
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_flip(horizontal, vertical);
+                        }
+                        _ => {}
+                    };
+                }
+                MainInterfaceCommand::Pivot(x, y) => {
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_pivot(x, y);
+                        }
+                        _ => {}
                     };
                 }
+                MainInterfaceCommand::Color(color) => {
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_color(color);
+                        }
+                        _ => {}
+                    };
+                }
+                MainInterfaceCommand::Keyframe(key) => {
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_keyframe(key);
+                        }
+                        _ => {}
+                    };
+                }
+                MainInterfaceCommand::Select(index) => {
+                    if index < doodads.len() {
+                        self.selected = index;
+
+                        let selected_doodad = &doodads[index];
+                        self.main_ui.change_settings(
+                            selected_doodad.get_scale(),
+                            selected_doodad.get_rotation() as f32,
+                            selected_doodad.get_flip(),
+                            selected_doodad.get_pivot(),
+                            selected_doodad.get_color(),
+                        );
+                    }
+                }
+                MainInterfaceCommand::Attach(parent) => {
+                    if self.selected < doodads.len() {
+                        match scene::set_parent(&mut doodads, self.selected, parent) {
+                            Ok(()) => {}
+                            Err(message) => self.main_menu_ui.show_error(message),
+                        }
+                    }
+                }
+                MainInterfaceCommand::Reorder(order) => {
+                    scene::set_layer_order(&mut doodads, &order);
+                }
+                MainInterfaceCommand::FrameLayer(layer) => {
+                    match doodads.get_mut(self.selected) {
+                        Some(selected_doodad) if !selected_doodad.is_locked() => {
+                            selected_doodad.set_frame_layer(layer);
+                        }
+                        _ => {}
+                    };
+                }
+                MainInterfaceCommand::Hide(index, hidden) => match doodads.get_mut(index) {
+                    Some(doodad) => doodad.set_hidden(hidden),
+                    None => {}
+                },
+                MainInterfaceCommand::Lock(index, locked) => match doodads.get_mut(index) {
+                    Some(doodad) => doodad.set_locked(locked),
+                    None => {}
+                },
+                MainInterfaceCommand::Duration(frame, duration) => {
+                    self.main_ui.set_duration(frame, duration);
+                }
+                MainInterfaceCommand::UniformDuration(duration) => {
+                    self.main_ui.set_uniform_duration(duration);
+                }
+                MainInterfaceCommand::Animation(index) => {
+                    let position = spritesheet.real_position();
+                    let definition = self.capture(&spritesheet, &doodads);
+
+                    match self.open_animation(
+                        &mut manager,
+                        definition,
+                        index,
+                        position.x,
+                        position.y,
+                        &mut spritesheet,
+                        &mut doodads,
+                    ) {
+                        Ok(()) => {}
+                        Err(message) => {
+                            //combo already shows the animation that could not be opened
+                            let (definition, current) = (self.definition.clone(), self.animation);
+                            self.set_definition(definition, current);
+                            self.main_menu_ui.show_error(message);
+                        }
+                    }
+                }
                 MainInterfaceCommand::Frame(frame) => {
                     for doodad in &mut doodads {
                         doodad.set_frame(frame as usize);
@@ -244,10 +865,15 @@ impl App {
                     spritesheet.set_frame(frame as usize);
                     self.frame = frame;
 
-                    match doodads.first() {
-                        Some(first_doodad) => {
-                            self.main_ui
-                                .set_rotation(first_doodad.get_rotation() as f32);
+                    match doodads.get(self.selected) {
+                        Some(selected_doodad) => {
+                            self.main_ui.change_settings(
+                                selected_doodad.get_scale(),
+                                selected_doodad.get_rotation() as f32,
+                                selected_doodad.get_flip(),
+                                selected_doodad.get_pivot(),
+                                selected_doodad.get_color(),
+                            );
                         }
                         None => {}
                     };
@@ -255,186 +881,397 @@ impl App {
                 _ => {}
             }
 
-            match self.main_menu_ui.check() {
+            match (edit, before) {
+                (Some(edit), Some(before)) => {
+                    let after = self.capture(&spritesheet, &doodads);
+
+                    if self.history.record(edit, before, after) {
+                        self.dirty = true;
+                    }
+                }
+                _ => {}
+            }
+
+            let command = self.main_menu_ui.check();
+            let edit = App::menu_edit(&command);
+            let before = match edit {
+                Some(_) => Some(self.capture(&spritesheet, &doodads)),
+                None => None,
+            };
+
+            //new, load and save leave the scene matching a file, or nothing worth recovering
+            let mut clean = false;
+
+            match command {
                 MainMenuCommand::New => {
                     doodads.clear();
+                    self.selected = 0;
+                    self.frame = 0;
+                    clean = true;
 
-                    spritesheet = Spritesheet::new(
-                        default_name.clone(),
-                        manager.get_spritesheet(&default_filename),
+                    match scene::spritesheet(
+                        &mut manager,
+                        &default_name,
+                        default_frames,
+                        None,
                         default_x,
                         default_y,
-                        default_frames,
-                    );
-                    self.main_ui.reset(default_frames as i32);
+                    ) {
+                        Ok(new_spritesheet) => spritesheet = new_spritesheet,
+                        Err(message) => self.main_menu_ui.show_error(message),
+                    }
+                    self.main_ui.reset(spritesheet.get_frames_amount() as i32);
+                    self.main_ui
+                        .set_durations(spritesheet.get_durations(self.default_duration));
+
+                    let starting = spritesheet.to_definition(self.main_ui.get_durations().clone());
+                    self.set_definition(AnimationDefinition::new(starting, Vec::new()), 0);
                 }
                 MainMenuCommand::Save(path) => {
-                    let mut temp_string = String::new();
-
-                    temp_string += &spritesheet.serialize();
-                    temp_string += &(self.main_ui.get_framerate().to_string());
+                    let definition = self.capture(&spritesheet, &doodads);
 
-                    for doodad in &doodads {
-                        temp_string += "\n";
-                        temp_string += &doodad.serialize(spritesheet.real_position().top_left());
-                    }
-
-                    file_utils::save_template(path, temp_string);
+                    definition::save(path, &definition);
+                    clean = true;
                 }
                 MainMenuCommand::Load(path) => {
-                    let lines = file_utils::load_file_by_lines(path);
+                    let loaded = definition::load(path.clone());
 
-                    let first = lines.first().unwrap();
-
-                    let split = file_utils::split_line(first, ";"); //name;frames;framerate
+                    if self.open_definition(
+                        &mut manager,
+                        loaded,
+                        default_x,
+                        default_y,
+                        &mut spritesheet,
+                        &mut doodads,
+                    ) {
+                        clean = true;
 
-                    let frames = split[1].parse::<usize>().unwrap();
+                        self.report_findings(&path);
+                    }
+                }
+                MainMenuCommand::Recover => {
+                    let loaded = definition::load_recovery();
 
-                    spritesheet = Spritesheet::new(
-                        split[0].clone(),
-                        manager.get_spritesheet(&(split[0].clone() + ".png")),
+                    self.open_definition(
+                        &mut manager,
+                        loaded,
                         default_x,
                         default_y,
-                        frames,
+                        &mut spritesheet,
+                        &mut doodads,
                     );
+                }
+                MainMenuCommand::ExportSpritesheet(name) => {
+                    let definition = self.current(&spritesheet, &doodads);
+                    let path = file_utils::spritesheet_path(&name);
+
+                    //bakes go next to the source art, so they never replace a sheet
+                    let baked = if path.exists() {
+                        Err(format!(
+                            "Spritesheet {} already exists, bake to another name",
+                            name
+                        ))
+                    } else {
+                        export::bake_spritesheet(&definition, &path)
+                    };
 
-                    self.main_ui.set_framerate(split[2].parse::<i32>().unwrap());
-
-                    self.main_ui.reset(frames as i32);
-                    doodads.clear();
-
-                    for line in lines.iter().skip(1) {
-                        let split = file_utils::split_line(line, ";"); //name;scale;posx,posy,rot/...
-
-                        let texture = manager.get_doodad(&(split[0].clone() + ".png"));
-                        let width = texture.query().width;
-                        let height = texture.query().height;
-
-                        let mut pos_vec: Vec<sdl2::rect::Rect> = Vec::new();
-                        let mut rot_vec: Vec<f64> = Vec::new();
-
-                        let split_positions = file_utils::split_line(&split[2], "/"); //data/data/data
-
-                        let split_positions: Vec<&String> = split_positions
-                            .iter()
-                            .filter(|position| position.len() != 0)
-                            .collect();
-
-                        for split_position in split_positions {
-                            let pos_data = file_utils::split_line(&split_position, ","); //posx,posy,rot
-
-                            let temp_x = default_x + pos_data[0].parse::<i32>().unwrap();
-                            let temp_y = default_y + pos_data[1].parse::<i32>().unwrap();
-                            let temp_rot = pos_data[2].parse::<f64>().unwrap();
-
-                            pos_vec.push(sdl2::rect::Rect::new(temp_x, temp_y, width, height));
-                            rot_vec.push(temp_rot);
-                        }
+                    match baked {
+                        Ok(()) => {}
+                        Err(message) => self.main_menu_ui.show_error(message),
+                    }
+                }
+                MainMenuCommand::ExportAnimation(name, options) => {
+                    let definition = self.current(&spritesheet, &doodads);
+
+                    let paths = file_utils::export_path(&name, "gif").and_then(|gif| {
+                        file_utils::export_path(&name, "png").map(|apng| (gif, apng))
+                    });
+
+                    let exported = paths
+                        .map_err(|err| err.to_string())
+                        .and_then(|(gif, apng)| {
+                            export::export_gif(&definition, &options, &gif)?;
+                            export::export_apng(&definition, &options, &apng)
+                        });
+
+                    match exported {
+                        Ok(()) => {}
+                        Err(message) => self.main_menu_ui.show_error(message),
+                    }
+                }
+                MainMenuCommand::ExportEngine(name) => {
+                    let definition = self.capture(&spritesheet, &doodads);
 
-                        let doodad = Doodad::load(
-                            split[0].clone(),
-                            texture,
-                            pos_vec,
-                            rot_vec,
-                            split[1].parse::<f32>().unwrap(),
-                        );
+                    let exported = file_utils::export_path(&name, "json")
+                        .map_err(|err| err.to_string())
+                        .and_then(|path| export::engine::export_engine(&definition, &path));
 
-                        doodads.push(doodad);
+                    match exported {
+                        Ok(()) => {}
+                        Err(message) => self.main_menu_ui.show_error(message),
                     }
                 }
+                MainMenuCommand::DiscardRecovery => {
+                    file_utils::remove_recovery();
+                }
                 MainMenuCommand::Exit => {
+                    file_utils::remove_recovery();
                     self.exit = true;
                 }
                 MainMenuCommand::AddDoodad(name) => {
                     let name_clone = name.clone();
 
-                    let texture = manager.get_doodad(&(name + ".png"));
+                    match manager.get_doodad(&(name + ".png")) {
+                        Ok(texture) => {
+                            let mut doodad = Doodad::new(
+                                name_clone,
+                                texture,
+                                default_x,
+                                default_y,
+                                spritesheet.get_frames_amount() as u32,
+                            );
+                            doodad.set_layer(scene::next_layer(&doodads));
 
-                    doodads.push(Doodad::new(
-                        name_clone,
-                        texture,
-                        default_x,
-                        default_y,
-                        spritesheet.get_frames_amount() as u32,
-                    ));
+                            doodads.push(doodad);
+                        }
+                        Err(message) => self.main_menu_ui.show_error(message),
+                    }
                 }
                 MainMenuCommand::ClearDoodads => {
                     doodads.clear();
+                    self.selected = 0;
                 }
-                MainMenuCommand::ChangeSpritesheet(name, frames) => {
-                    let name_clone = name.clone();
-
-                    let texture = manager.get_spritesheet(&(name + ".png"));
-
+                MainMenuCommand::ChangeSpritesheet(name, frames, grid) => {
                     let position = spritesheet.real_position();
 
-                    spritesheet = Spritesheet::new(
-                        name_clone,
-                        texture,
+                    //the frames typed in only count for grids and sheets without an atlas
+                    match scene::spritesheet(
+                        &mut manager,
+                        &name,
+                        frames as usize,
+                        grid,
                         position.x,
                         position.y,
-                        frames as usize,
-                    );
+                    ) {
+                        Ok(new_spritesheet) => {
+                            let fallback = self.fallback_duration();
+                            spritesheet = new_spritesheet;
 
-                    for doodad in &mut doodads {
-                        doodad.set_frames_amount(frames.into());
+                            for doodad in &mut doodads {
+                                doodad.set_frames_amount(spritesheet.get_frames_amount());
+                            }
+
+                            self.main_ui.reset(spritesheet.get_frames_amount() as i32);
+                            self.main_ui
+                                .set_durations(spritesheet.get_durations(fallback));
+                        }
+                        Err(message) => self.main_menu_ui.show_error(message),
+                    }
+                }
+                MainMenuCommand::AddAnimation(name, sheet, frames, grid) => {
+                    let position = spritesheet.real_position();
+
+                    //like ChangeSpritesheet, an atlas next to the sheet decides the frame count
+                    let added = match self.definition.find_animation(&name) {
+                        Some(_) => Err(format!("There already is an animation named {}", name)),
+                        None => {
+                            scene::spritesheet(&mut manager, &sheet, frames as usize, grid, 0, 0)
+                        }
+                    }
+                    .and_then(|new_spritesheet| {
+                        let mut definition = self.capture(&spritesheet, &doodads);
+                        let index = definition.add_animation(
+                            name,
+                            new_spritesheet.to_definition(
+                                new_spritesheet.get_durations(self.fallback_duration()),
+                            ),
+                            self.animation,
+                        );
+
+                        self.open_animation(
+                            &mut manager,
+                            definition,
+                            index,
+                            position.x,
+                            position.y,
+                            &mut spritesheet,
+                            &mut doodads,
+                        )
+                    });
+
+                    match added {
+                        Ok(()) => {}
+                        Err(message) => self.main_menu_ui.show_error(message),
                     }
+                }
+                MainMenuCommand::RemoveAnimation => {
+                    let position = spritesheet.real_position();
+                    let mut definition = self.capture(&spritesheet, &doodads);
+
+                    let removed = if definition.animations.len() == 1 {
+                        Err("The last animation can not be removed".to_string())
+                    } else {
+                        definition.animations.remove(self.animation);
+                        let index = self.animation.saturating_sub(1);
+
+                        self.open_animation(
+                            &mut manager,
+                            definition,
+                            index,
+                            position.x,
+                            position.y,
+                            &mut spritesheet,
+                            &mut doodads,
+                        )
+                    };
 
-                    self.main_ui.reset(frames as i32);
+                    match removed {
+                        Ok(()) => {}
+                        Err(message) => self.main_menu_ui.show_error(message),
+                    }
                 }
+                MainMenuCommand::Undo => match self.history.undo() {
+                    Some(definition) => {
+                        self.restore(&mut manager, &definition, &mut spritesheet, &mut doodads)
+                    }
+                    None => {}
+                },
+                MainMenuCommand::Redo => match self.history.redo() {
+                    Some(definition) => {
+                        self.restore(&mut manager, &definition, &mut spritesheet, &mut doodads)
+                    }
+                    None => {}
+                },
                 _ => {}
             }
 
+            match (edit, before) {
+                (Some(edit), Some(before)) => {
+                    let after = self.capture(&spritesheet, &doodads);
+
+                    if self.history.record(edit, before, after) {
+                        self.dirty = true;
+                    }
+                }
+                _ => {}
+            }
+
+            if clean {
+                self.dirty = false;
+                file_utils::remove_recovery();
+            }
+
+            //timer only runs while there are unsaved changes
+            if !self.dirty {
+                self.autosave_timer.reset();
+            } else if self.autosave_timer.did_pass(self.autosave_interval) {
+                let definition = self.capture(&spritesheet, &doodads);
+
+                match definition::autosave(&definition) {
+                    Ok(()) => {}
+                    Err(err) => self
+                        .main_menu_ui
+                        .show_error(format!("Autosave failed: {}", err)),
+                }
+
+                self.autosave_timer.reset();
+            }
+
+            self.main_menu_ui
+                .set_history(self.history.next_undo(), self.history.next_redo());
+            self.main_menu_ui.set_dirty(self.dirty);
+
+            //children follow whatever happened to their parents this frame
+            scene::place(&mut doodads);
+
             canvas.set_draw_color(self.config.read_color("background_color"));
             canvas.clear();
 
-            canvas
-                .set_scale(spritesheet.get_scale(), spritesheet.get_scale())
-                .unwrap();
-
-            canvas
-                .copy_ex(
-                    spritesheet.get_texture(),
-                    Some(spritesheet.get_source_rect()),
-                    Some(spritesheet.draw_position()),
-                    spritesheet.get_rotation(),
-                    None,
-                    false,
-                    false,
+            match self.main_ui.get_onion_skin() {
+                Some(neighbours) => scene::draw_onion_skin(
+                    &mut canvas,
+                    &mut spritesheet,
+                    &mut doodads,
+                    self.frame as usize,
+                    neighbours,
                 )
-                .unwrap();
-
-            for doodad in doodads.iter().rev() {
-                canvas
-                    .set_scale(doodad.get_scale(), doodad.get_scale())
-                    .unwrap();
-
-                canvas
-                    .copy_ex(
-                        doodad.get_texture(),
-                        Some(doodad.get_source_rect()),
-                        Some(doodad.draw_position()),
-                        doodad.get_rotation(),
-                        None,
-                        false,
-                        false,
-                    )
-                    .unwrap();
+                .unwrap(),
+                None => {}
             }
 
-            if doodads.len() != 0 {
-                let first = doodads.first().unwrap();
+            scene::draw(&mut canvas, &spritesheet, &doodads).unwrap();
+
+            if self.selected < doodads.len() {
+                let active = &doodads[self.selected];
+
+                if self.main_ui.get_motion_path() {
+                    App::draw_motion_path(&mut canvas, &active.path(), self.frame as usize);
+                }
 
                 App::draw_rectangle_around_active(
                     &mut canvas,
                     rotate_rectangle(
-                        first.real_position(),
-                        first.get_rotation() as f32,
-                        first.get_scale(),
+                        active.real_position(),
+                        active.get_rotation() as f32,
+                        active.get_scale(),
+                        active.get_center(),
+                        active.get_flip(),
                     ),
+                    active.pivot_position(),
                 );
             }
 
+            match self.main_menu_ui.slicing_preview() {
+                Some((name, grid, frames)) => match manager.get_spritesheet(&(name + ".png")) {
+                    Ok(texture) => App::draw_slicing_preview(
+                        &mut canvas,
+                        &texture,
+                        &grid,
+                        frames,
+                        spritesheet.real_position().top_left(),
+                    ),
+                    Err(_) => {}
+                },
+                None => {}
+            }
+
+            match self.main_ui.get_timeline() {
+                Some((area, slots)) => App::draw_timeline(&mut canvas, &spritesheet, area, &slots),
+                None => {}
+            }
+
+            let origin = spritesheet.real_position().top_left();
+            self.main_ui.set_tracks(
+                doodads
+                    .iter()
+                    .map(|doodad| {
+                        let track = doodad.to_track(origin);
+                        let adjusted = track.adjusted();
+
+                        (track.name, adjusted, doodad.get_keyframes())
+                    })
+                    .collect(),
+            );
+            self.main_ui.set_keyframe(
+                doodads
+                    .get(self.selected)
+                    .and_then(|doodad| doodad.get_keyframe()),
+            );
+            self.main_ui.set_doodads(
+                doodads
+                    .iter()
+                    .map(|doodad| DoodadRow {
+                        name: imgui::ImString::new(doodad.get_name()),
+                        parent: doodad.get_parent(),
+                        layer: doodad.get_base_layer(),
+                        frame_layer: doodad.get_frame_layer(),
+                        hidden: doodad.is_hidden(),
+                        locked: doodad.is_locked(),
+                    })
+                    .collect(),
+                self.selected,
+            );
+
             let ui = imgui_sdl2.frame(&canvas.window(), &mut imgui, &event_pump);
 
             self.main_ui.draw_window(&ui);
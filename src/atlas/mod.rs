@@ -0,0 +1,455 @@
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json;
+use std::fmt;
+use std::path::Path;
+
+use definition::{Grid, SpritesheetDefinition};
+use file_utils;
+
+//frame rectangles of a spritesheet, read from a texturepacker or aseprite json next to the png
+//sheets without one are sliced into a single row of equally wide frames
+//a grid in the definition slices the sheet into rows and columns instead
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasFrame {
+    //part of the png holding the frame
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    //trimmed frames are smaller than the atlas frame size, this is where they sit inside it
+    pub offset_x: i32,
+    pub offset_y: i32,
+    //milliseconds, only where a spritesheet starts from, the definition keeps its own durations
+    pub duration: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Atlas {
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub frames: Vec<AtlasFrame>,
+}
+
+#[derive(Deserialize)]
+struct RawRect {
+    x: i32,
+    y: i32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct RawSize {
+    w: u32,
+    h: u32,
+}
+
+//texturepacker and aseprite share the layout, aseprite adds the durations
+#[derive(Deserialize)]
+struct RawFrame {
+    frame: RawRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(rename = "spriteSourceSize")]
+    sprite_source_size: Option<RawRect>,
+    #[serde(rename = "sourceSize")]
+    source_size: Option<RawSize>,
+    duration: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFrames {
+    Array(Vec<RawFrame>),
+    Hash(NamedFrames),
+}
+
+//frames keyed by their names, kept in the order they are written in
+struct NamedFrames(Vec<(String, RawFrame)>);
+
+impl<'de> Deserialize<'de> for NamedFrames {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(NamedFramesVisitor)
+    }
+}
+
+struct NamedFramesVisitor;
+
+impl<'de> Visitor<'de> for NamedFramesVisitor {
+    type Value = NamedFrames;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "frames keyed by their names")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<NamedFrames, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut frames = Vec::new();
+
+        while let Some(entry) = map.next_entry()? {
+            frames.push(entry);
+        }
+
+        Ok(NamedFrames(frames))
+    }
+}
+
+#[derive(Deserialize)]
+struct RawAtlas {
+    frames: RawFrames,
+}
+
+impl Atlas {
+    //the slicing Spritesheet always did, frames of a strip are left to right
+    pub fn uniform(width: u32, height: u32, frame_count: usize) -> Self {
+        let frame_width = width / frame_count.max(1) as u32;
+
+        Atlas {
+            frame_width: frame_width,
+            frame_height: height,
+            frames: (0..frame_count)
+                .map(|index| AtlasFrame {
+                    x: index as i32 * frame_width as i32,
+                    y: 0,
+                    width: frame_width,
+                    height: height,
+                    offset_x: 0,
+                    offset_y: 0,
+                    duration: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn grid(grid: &Grid, frame_count: usize) -> Result<Self, String> {
+        let mut frames = Vec::new();
+
+        for index in 0..frame_count {
+            let (x, y) = grid_cell(grid, index).ok_or_else(|| too_large(grid))?;
+
+            frames.push(AtlasFrame {
+                x: x,
+                y: y,
+                width: grid.frame_width,
+                height: grid.frame_height,
+                offset_x: 0,
+                offset_y: 0,
+                duration: None,
+            });
+        }
+
+        Ok(Atlas {
+            frame_width: grid.frame_width,
+            frame_height: grid.frame_height,
+            frames: frames,
+        })
+    }
+
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let raw: RawAtlas = serde_json::from_str(data).map_err(|err| err.to_string())?;
+
+        let raw_frames = match raw.frames {
+            RawFrames::Array(frames) => frames,
+            //exporters write the frames in animation order, their names do not always sort that way
+            RawFrames::Hash(NamedFrames(frames)) => {
+                frames.into_iter().map(|(_, frame)| frame).collect()
+            }
+        };
+
+        if raw_frames.len() == 0 {
+            return Err("Atlas has no frames".to_string());
+        }
+
+        let mut frame_width = 0;
+        let mut frame_height = 0;
+        let mut frames = Vec::new();
+
+        for (index, raw) in raw_frames.into_iter().enumerate() {
+            if raw.rotated {
+                return Err(format!(
+                    "Frame {} is rotated, export the atlas without rotation",
+                    index
+                ));
+            }
+
+            let (offset_x, offset_y) = match raw.sprite_source_size {
+                Some(ref trimmed) => (trimmed.x, trimmed.y),
+                None => (0, 0),
+            };
+            let (width, height) = match raw.source_size {
+                Some(ref size) => (size.w, size.h),
+                None => (raw.frame.w, raw.frame.h),
+            };
+
+            frame_width = frame_width.max(width);
+            frame_height = frame_height.max(height);
+
+            frames.push(AtlasFrame {
+                x: raw.frame.x,
+                y: raw.frame.y,
+                width: raw.frame.w,
+                height: raw.frame.h,
+                offset_x: offset_x,
+                offset_y: offset_y,
+                duration: raw.duration,
+            });
+        }
+
+        Ok(Atlas {
+            frame_width: frame_width,
+            frame_height: frame_height,
+            frames: frames,
+        })
+    }
+}
+
+//none when the spritesheet has no sidecar
+pub fn load(name: &str) -> Result<Option<Atlas>, String> {
+    let path = file_utils::atlas_path(name);
+
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let data = file_utils::load_file(&path).map_err(|err| err.to_string())?;
+
+    Atlas::parse(&data)
+        .map(Some)
+        .map_err(|message| format!("{}: {}", path.display(), message))
+}
+
+//frames of a spritesheet as the editor slices them, a grid set in the definition wins over the
+//sidecar and the frame count is only used without one
+pub fn resolve(definition: &SpritesheetDefinition) -> Result<Atlas, String> {
+    let name = &definition.name;
+
+    match definition.grid {
+        Some(ref grid) => return Atlas::grid(grid, definition.frames),
+        None => {}
+    }
+
+    match load(name)? {
+        Some(atlas) => Ok(atlas),
+        None => {
+            let (width, height) = file_utils::image_size(&file_utils::spritesheet_path(name))?;
+
+            Ok(Atlas::uniform(width, height, definition.frames))
+        }
+    }
+}
+
+//top left corner of a cell, counted over the whole grid and not only the frames
+//none when the corner is further away than a texture coordinate can reach
+pub fn grid_cell(grid: &Grid, index: usize) -> Option<(i32, i32)> {
+    let columns = grid.columns.max(1) as usize;
+
+    let offset = |cell: usize, size: u32| {
+        (cell as u64)
+            .checked_mul(size as u64 + grid.spacing as u64)
+            .and_then(|offset| offset.checked_add(grid.margin as u64))
+            .filter(|&offset| offset <= i32::max_value() as u64)
+            .map(|offset| offset as i32)
+    };
+
+    match (
+        offset(index % columns, grid.frame_width),
+        offset(index / columns, grid.frame_height),
+    ) {
+        (Some(x), Some(y)) => Some((x, y)),
+        _ => None,
+    }
+}
+
+//frame size that fills the image when only the cell counts are known
+pub fn fit_grid(grid: &Grid, width: u32, height: u32) -> Grid {
+    let fit = |size: u32, cells: u32| {
+        let cells = cells.max(1);
+        size.saturating_sub(2 * grid.margin + (cells - 1) * grid.spacing) / cells
+    };
+
+    Grid {
+        frame_width: fit(width, grid.columns),
+        frame_height: fit(height, grid.rows),
+        ..*grid
+    }
+}
+
+pub fn check_grid(grid: &Grid, width: u32, height: u32, frame_count: usize) -> Result<(), String> {
+    if grid.columns == 0 || grid.rows == 0 || grid.frame_width == 0 || grid.frame_height == 0 {
+        return Err("Grid needs at least one cell of at least one pixel".to_string());
+    }
+
+    let cells = grid
+        .columns
+        .checked_mul(grid.rows)
+        .ok_or_else(|| too_large(grid))? as usize;
+    if frame_count > cells {
+        return Err(format!(
+            "Grid has {} cells but there are {} frames",
+            cells, frame_count
+        ));
+    }
+
+    let (x, y) = grid_cell(grid, cells - 1).ok_or_else(|| too_large(grid))?;
+    let right = (x as u32).checked_add(grid.frame_width);
+    let bottom = (y as u32).checked_add(grid.frame_height);
+
+    match (right, bottom) {
+        (Some(right), Some(bottom)) if right <= width && bottom <= height => Ok(()),
+        (Some(right), Some(bottom)) => Err(format!(
+            "Grid needs {}x{} pixels but the image is {}x{}",
+            right, bottom, width, height
+        )),
+        _ => Err(too_large(grid)),
+    }
+}
+
+fn too_large(grid: &Grid) -> String {
+    format!(
+        "Grid of {}x{} cells of {}x{} pixels is larger than any image",
+        grid.columns, grid.rows, grid.frame_width, grid.frame_height
+    )
+}
+
+//baking over a sheet with a sidecar would leave the json slicing a strip it does not describe
+pub fn has_sidecar(image: &Path) -> bool {
+    image.with_extension("json").exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(x: i32, width: u32, offset_x: i32, duration: Option<u32>) -> AtlasFrame {
+        AtlasFrame {
+            x: x,
+            y: 0,
+            width: width,
+            height: 32,
+            offset_x: offset_x,
+            offset_y: 0,
+            duration: duration,
+        }
+    }
+
+    #[test]
+    fn array_layout() {
+        let atlas = Atlas::parse(
+            r#"{
+                "frames": [
+                    { "filename": "a", "frame": { "x": 0, "y": 0, "w": 32, "h": 32 } },
+                    {
+                        "filename": "b",
+                        "frame": { "x": 32, "y": 0, "w": 20, "h": 32 },
+                        "rotated": false,
+                        "spriteSourceSize": { "x": 6, "y": 0, "w": 20, "h": 32 },
+                        "sourceSize": { "w": 40, "h": 32 }
+                    }
+                ],
+                "meta": {}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            atlas,
+            Atlas {
+                frame_width: 40,
+                frame_height: 32,
+                frames: vec![frame(0, 32, 0, None), frame(32, 20, 6, None)],
+            }
+        );
+    }
+
+    #[test]
+    fn hash_layout_keeps_the_order_of_the_file() {
+        let atlas = Atlas::parse(
+            r#"{
+                "frames": {
+                    "walk 10.png": { "frame": { "x": 64, "y": 0, "w": 32, "h": 32 }, "duration": 80 },
+                    "walk 2.png": { "frame": { "x": 32, "y": 0, "w": 32, "h": 32 }, "duration": 90 },
+                    "walk 1.png": { "frame": { "x": 0, "y": 0, "w": 32, "h": 32 }, "duration": 100 }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            atlas.frames,
+            vec![
+                frame(64, 32, 0, Some(80)),
+                frame(32, 32, 0, Some(90)),
+                frame(0, 32, 0, Some(100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn huge_grids_are_rejected_without_overflowing() {
+        let grid = Grid {
+            columns: u32::max_value(),
+            rows: 2,
+            frame_width: 1,
+            frame_height: 1,
+            margin: 0,
+            spacing: 0,
+        };
+        assert_eq!(check_grid(&grid, 64, 64, 1), Err(too_large(&grid)));
+
+        let grid = Grid {
+            columns: 2,
+            rows: 1,
+            frame_width: u32::max_value(),
+            frame_height: 1,
+            margin: 0,
+            spacing: 1,
+        };
+        assert_eq!(grid_cell(&grid, 1), None);
+        assert_eq!(check_grid(&grid, 64, 64, 1), Err(too_large(&grid)));
+        assert_eq!(Atlas::grid(&grid, 2), Err(too_large(&grid)));
+    }
+
+    #[test]
+    fn grid_cells_count_margin_and_spacing() {
+        let grid = Grid {
+            columns: 3,
+            rows: 2,
+            frame_width: 10,
+            frame_height: 20,
+            margin: 2,
+            spacing: 1,
+        };
+
+        assert_eq!(grid_cell(&grid, 4), Some((13, 23)));
+        assert_eq!(check_grid(&grid, 34, 43, 6), Ok(()));
+        assert_eq!(
+            check_grid(&grid, 33, 43, 6),
+            Err("Grid needs 34x43 pixels but the image is 33x43".to_string())
+        );
+        assert_eq!(
+            check_grid(&grid, 34, 43, 7),
+            Err("Grid has 6 cells but there are 7 frames".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_rotated_and_empty_atlases() {
+        assert_eq!(
+            Atlas::parse(
+                r#"{ "frames": [{ "frame": { "x": 0, "y": 0, "w": 1, "h": 1 }, "rotated": true }] }"#
+            ),
+            Err("Frame 0 is rotated, export the atlas without rotation".to_string())
+        );
+        assert_eq!(
+            Atlas::parse(r#"{ "frames": {} }"#),
+            Err("Atlas has no frames".to_string())
+        );
+        assert!(Atlas::parse(r#"{ "frames": 3 }"#).is_err());
+    }
+}
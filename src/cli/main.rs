@@ -0,0 +1,360 @@
+//headless counterpart of the editor, renders definitions without a window or an opengl context
+//paths are relative to the project root just like in the editor, see --root
+
+extern crate gif;
+extern crate imgui;
+extern crate png;
+extern crate sdl2;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_path_to_error;
+
+//the editor uses the rest of the modules marked dead_code and still reports what neither
+//binary uses, the ones without the mark are used in full here
+#[path = "../atlas/mod.rs"]
+#[allow(dead_code)]
+mod atlas;
+#[path = "../definition/mod.rs"]
+#[allow(dead_code)]
+mod definition;
+#[path = "../export/mod.rs"]
+mod export;
+#[path = "../file_utils/mod.rs"]
+#[allow(dead_code)]
+mod file_utils;
+#[path = "../fragment/mod.rs"]
+#[allow(dead_code)]
+mod fragment;
+#[path = "../hierarchy/mod.rs"]
+#[allow(dead_code)]
+mod hierarchy;
+#[path = "../lint/mod.rs"]
+mod lint;
+#[path = "../mymath/mod.rs"]
+#[allow(dead_code)]
+mod mymath;
+#[path = "../resource_manager/mod.rs"]
+mod resource_manager;
+#[path = "../scene/mod.rs"]
+#[allow(dead_code)]
+mod scene;
+#[path = "../tween/mod.rs"]
+#[allow(dead_code)]
+mod tween;
+
+use sdl2::image::InitFlag;
+use sdl2::pixels::Color;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use definition::{Animation, AnimationDefinition, DefinitionError};
+use export::AnimationExport;
+
+const USAGE: &'static str = "usage:
+    sprite-cli [--root <dir>] render <definition> <output> [options]
+        --format <strip|frames|gif|apng|engine>
+                                           defaults to gif for .gif outputs, engine for .json
+                                           outputs and strip otherwise, strips never replace
+                                           an existing file
+        --scale <factor>                   gif, apng and frames only, defaults to 1
+        --background <r,g,b>               gif, apng and frames only, transparent by default
+        --loops <count>                    gif and apng only, 0 loops forever
+        --animation <name>                 defaults to the first one, engine exports all of them
+    sprite-cli [--root <dir>] validate <definition>...
+        reports every finding with its line, fails when any of them is an error
+    sprite-cli [--root <dir>] info <definition>
+
+definition is either a path to a .json or .csv file or a name from resources/definitions";
+
+enum Format {
+    Strip,
+    Frames,
+    Gif,
+    Apng,
+    Engine,
+}
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    match take_option(&mut args, "--root") {
+        Ok(Some(root)) => {
+            if let Err(err) = env::set_current_dir(&root) {
+                fail(format!("Could not enter {}: {}", root, err));
+            }
+        }
+        Ok(None) => {}
+        Err(message) => fail(message),
+    }
+
+    if args.len() == 0 {
+        fail(USAGE.to_string());
+    }
+
+    let command = args.remove(0);
+
+    let result = match command.as_str() {
+        "render" => render(args),
+        "validate" => validate(args),
+        "info" => info(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("Unknown command: {}\n\n{}", command, USAGE)),
+    };
+
+    match result {
+        Ok(()) => {}
+        Err(message) => fail(message),
+    }
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+//removes "--name value" from the arguments, so the positional ones are left behind
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            if index + 1 >= args.len() {
+                return Err(format!("Missing value for {}", name));
+            }
+
+            let value = args.remove(index + 1);
+            args.remove(index);
+
+            Ok(Some(value))
+        }
+        None => Ok(None),
+    }
+}
+
+fn parse_option<T: std::str::FromStr>(
+    value: Option<String>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    match value {
+        Some(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        None => Ok(None),
+    }
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    let channels: Vec<Result<u8, _>> = value
+        .split(',')
+        .map(|channel| channel.trim().parse::<u8>())
+        .collect();
+
+    match channels.as_slice() {
+        [Ok(red), Ok(green), Ok(blue)] => Ok(Color::RGB(*red, *green, *blue)),
+        _ => Err(format!("Invalid color, expected r,g,b: {}", value)),
+    }
+}
+
+fn resolve(argument: &str) -> Option<PathBuf> {
+    let path = Path::new(argument);
+
+    if path.is_file() {
+        Some(path.to_path_buf())
+    } else {
+        file_utils::find_template(argument)
+    }
+}
+
+fn load(argument: &str) -> Result<AnimationDefinition, DefinitionError> {
+    match resolve(argument) {
+        Some(path) => definition::load_path(&path),
+        None => definition::load(argument.to_string()),
+    }
+}
+
+fn render(mut args: Vec<String>) -> Result<(), String> {
+    let format = take_option(&mut args, "--format")?;
+    let scale = parse_option::<f32>(take_option(&mut args, "--scale")?, "--scale")?;
+    let loops = parse_option::<u16>(take_option(&mut args, "--loops")?, "--loops")?;
+    let background = match take_option(&mut args, "--background")? {
+        Some(value) => Some(parse_color(&value)?),
+        None => None,
+    };
+    let animation = take_option(&mut args, "--animation")?;
+
+    if args.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+
+    let output = Path::new(&args[1]);
+
+    let format = match format.as_ref().map(String::as_str) {
+        Some("strip") => Format::Strip,
+        Some("frames") => Format::Frames,
+        Some("gif") => Format::Gif,
+        Some("apng") => Format::Apng,
+        Some("engine") => Format::Engine,
+        Some(other) => return Err(format!("Unknown format: {}", other)),
+        None => match output.extension() {
+            Some(extension) if extension == "gif" => Format::Gif,
+            Some(extension) if extension == "json" => Format::Engine,
+            _ => Format::Strip,
+        },
+    };
+
+    let options = AnimationExport {
+        scale: scale.unwrap_or(1.0),
+        background: background,
+        loops: loops.unwrap_or(0),
+    };
+
+    let definition = load(&args[0]).map_err(|err| err.to_string())?;
+    let clip = pick_animation(&definition, animation)?;
+
+    let _image = sdl2::image::init(InitFlag::PNG)?;
+
+    match format {
+        //like bakes in the editor, so a source spritesheet is never overwritten
+        Format::Strip if output.exists() => {
+            return Err(format!(
+                "{} already exists, bake to another name",
+                output.display()
+            ))
+        }
+        Format::Strip => export::bake_spritesheet(&clip, output)?,
+        Format::Frames => {
+            std::fs::create_dir_all(output).map_err(|err| err.to_string())?;
+            export::export_frames(&clip, &options, output)?;
+        }
+        Format::Gif => export::export_gif(&clip, &options, output)?,
+        Format::Apng => export::export_apng(&clip, &options, output)?,
+        Format::Engine => export::engine::export_engine(&definition, output)?,
+    }
+
+    println!("{}", output.display());
+
+    Ok(())
+}
+
+fn pick_animation(
+    definition: &AnimationDefinition,
+    name: Option<String>,
+) -> Result<Animation, String> {
+    match name {
+        Some(name) => match definition.find_animation(&name) {
+            Some(index) => Ok(definition.animation(index)),
+            None => Err(format!("Unknown animation: {}", name)),
+        },
+        None => Ok(definition.animation(0)),
+    }
+}
+
+fn validate(args: Vec<String>) -> Result<(), String> {
+    if args.len() == 0 {
+        return Err(USAGE.to_string());
+    }
+
+    let mut failed = 0;
+
+    for argument in &args {
+        let findings = match resolve(argument) {
+            Some(path) => lint::lint_file(&path),
+            None => Err(
+                DefinitionError::new(0, 0, "", "Definition not found".to_string())
+                    .in_file(argument),
+            ),
+        };
+
+        match findings {
+            Ok(findings) => {
+                for finding in &findings {
+                    println!("{}:{}", argument, finding);
+                }
+
+                if lint::has_errors(&findings) {
+                    failed += 1;
+                } else {
+                    println!("{}: ok", argument);
+                }
+            }
+            Err(err) => {
+                println!("{}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} definitions failed", failed, args.len())),
+    }
+}
+
+fn info(args: Vec<String>) -> Result<(), String> {
+    if args.len() != 1 {
+        return Err(USAGE.to_string());
+    }
+
+    let definition = load(&args[0]).map_err(|err| err.to_string())?;
+
+    println!("version: {}", definition.version);
+
+    println!("doodads: {}", definition.doodads.len());
+    for doodad in &definition.doodads {
+        match doodad
+            .parent
+            .and_then(|parent| definition.doodads.get(parent))
+        {
+            Some(parent) => println!("    {} attached to {}", doodad.name, parent.name),
+            None => println!("    {}", doodad.name),
+        }
+    }
+
+    println!("animations: {}", definition.animations.len());
+    for clip in &definition.animations {
+        let spritesheet = &clip.spritesheet;
+
+        println!("    {}", clip.name);
+        println!("        spritesheet: {}", spritesheet.name);
+        println!("        frames: {}", spritesheet.frames);
+        println!(
+            "        durations: {} ms",
+            spritesheet
+                .durations
+                .iter()
+                .map(|duration| duration.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        match atlas::resolve(spritesheet) {
+            Ok(atlas) => {
+                println!(
+                    "        frame size: {}x{}",
+                    atlas.frame_width, atlas.frame_height
+                );
+
+                match spritesheet.grid {
+                    Some(grid) => println!(
+                        "        slicing: {}x{} grid, margin {}, spacing {}",
+                        grid.columns, grid.rows, grid.margin, grid.spacing
+                    ),
+                    None => match file_utils::atlas_path(&spritesheet.name).exists() {
+                        true => {
+                            println!("        slicing: atlas with {} frames", atlas.frames.len())
+                        }
+                        false => println!("        slicing: uniform strip"),
+                    },
+                }
+            }
+            Err(message) => println!("        frame size: unknown ({})", message),
+        }
+    }
+
+    Ok(())
+}
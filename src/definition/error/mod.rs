@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fmt;
+
+//line and column start at 1, 0 means the problem is not tied to a place in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinitionError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub field: String,
+    pub message: String,
+}
+
+impl DefinitionError {
+    pub fn new(line: usize, column: usize, field: &str, message: String) -> Self {
+        DefinitionError {
+            file: String::new(),
+            line: line,
+            column: column,
+            field: field.to_string(),
+            message: message,
+        }
+    }
+
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.file = file.to_string();
+        self
+    }
+}
+
+impl fmt::Display for DefinitionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )?;
+
+        if self.field.len() != 0 {
+            write!(f, " (field: {})", self.field)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for DefinitionError {}
@@ -0,0 +1,335 @@
+use definition::{
+    AnimationDefinition, DefinitionError, DoodadTrack, FrameTransform, SpritesheetDefinition,
+};
+
+//reader for the pre-json definitions, every one of them is migrated to the current version
+//header: name;frames;framerate
+//doodad: name;scale;posx,posy,rot/posx,posy,rot/...
+//oldest doodad: name;(posx,posy).(posx,posy).;rot.rot.
+
+//part of a line together with the column it starts at
+type Field<'a> = (usize, &'a str);
+
+pub fn parse(data: &str) -> Result<AnimationDefinition, DefinitionError> {
+    let mut lines = data
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|&(_, line)| line.len() != 0);
+
+    let spritesheet = match lines.next() {
+        Some((number, header)) => parse_header(number, header)?,
+        None => {
+            return Err(DefinitionError::new(
+                1,
+                1,
+                "",
+                "Definition is empty".to_string(),
+            ))
+        }
+    };
+
+    let mut doodads = Vec::new();
+    for (number, line) in lines {
+        doodads.push(parse_doodad(number, line)?);
+    }
+
+    Ok(AnimationDefinition::new(spritesheet, doodads))
+}
+
+fn parse_header(number: usize, line: &str) -> Result<SpritesheetDefinition, DefinitionError> {
+    let split = split_columns(line, 1, ';'); //name;frames;framerate
+
+    let frames: usize = parse_field(number, field(number, line, &split, 1, "frames")?, "frames")?;
+    if frames == 0 {
+        return Err(DefinitionError::new(
+            number,
+            split[1].0,
+            "frames",
+            "Spritesheet needs at least one frame".to_string(),
+        ));
+    }
+
+    let framerate: u32 = parse_field(
+        number,
+        field(number, line, &split, 2, "framerate")?,
+        "framerate",
+    )?;
+
+    Ok(SpritesheetDefinition {
+        name: split[0].1.to_string(),
+        frames: frames,
+        durations: vec![framerate; frames],
+        grid: None,
+    })
+}
+
+fn parse_doodad(number: usize, line: &str) -> Result<DoodadTrack, DefinitionError> {
+    let split = split_columns(line, 1, ';');
+
+    let second = field(number, line, &split, 1, "scale")?;
+
+    if second.1.starts_with('(') {
+        return parse_dotted_doodad(number, line, &split);
+    }
+
+    let positions = field(number, line, &split, 2, "positions")?;
+
+    let scale = parse_field(number, second, "scale")?;
+
+    let mut frames = Vec::new();
+
+    for position in split_columns(positions.1, positions.0, '/')
+        .into_iter()
+        .filter(|&(_, position)| position.len() != 0)
+    {
+        let pos_data = split_columns(position.1, position.0, ','); //posx,posy,rot
+
+        frames.push(FrameTransform {
+            x: parse_field(number, pos_data[0], "x")?,
+            y: parse_field(number, field(number, line, &pos_data, 1, "y")?, "y")?,
+            rotation: parse_field(
+                number,
+                field(number, line, &pos_data, 2, "rotation")?,
+                "rotation",
+            )?,
+            scale: scale,
+            flip_h: false,
+            flip_v: false,
+            alpha: 255,
+            tint: [255, 255, 255],
+            key: None,
+            layer: None,
+        });
+    }
+
+    if frames.len() == 0 {
+        return Err(DefinitionError::new(
+            number,
+            positions.0,
+            "positions",
+            format!("Doodad {} has no frames", split[0].1),
+        ));
+    }
+
+    Ok(DoodadTrack {
+        name: split[0].1.to_string(),
+        pivot: None,
+        parent: None,
+        layer: 0,
+        hidden: false,
+        locked: false,
+        frames: frames,
+    })
+}
+
+fn parse_dotted_doodad(
+    number: usize,
+    line: &str,
+    split: &Vec<Field>,
+) -> Result<DoodadTrack, DefinitionError> {
+    let positions: Vec<Field> = split_columns(split[1].1, split[1].0, '.')
+        .into_iter()
+        .filter(|&(_, position)| position.len() != 0)
+        .collect();
+    let rotations = split_dotted_rotations(number, field(number, line, split, 2, "rotations")?)?;
+
+    if positions.len() != rotations.len() || positions.len() == 0 {
+        return Err(DefinitionError::new(
+            number,
+            split[2].0,
+            "rotations",
+            format!(
+                "Doodad {} has {} positions but {} rotations",
+                split[0].1,
+                positions.len(),
+                rotations.len()
+            ),
+        ));
+    }
+
+    let mut frames = Vec::new();
+
+    for (position, rotation) in positions.into_iter().zip(rotations) {
+        let trimmed = position.1.trim_start_matches('(').trim_end_matches(')');
+        let pos_data = split_columns(trimmed, position.0 + 1, ','); //(posx,posy)
+
+        frames.push(FrameTransform {
+            x: parse_field(number, pos_data[0], "x")?,
+            y: parse_field(number, field(number, line, &pos_data, 1, "y")?, "y")?,
+            rotation: rotation,
+            scale: 1.0,
+            flip_h: false,
+            flip_v: false,
+            alpha: 255,
+            tint: [255, 255, 255],
+            key: None,
+            layer: None,
+        });
+    }
+
+    Ok(DoodadTrack {
+        name: split[0].1.to_string(),
+        pivot: None,
+        parent: None,
+        layer: 0,
+        hidden: false,
+        locked: false,
+        frames: frames,
+    })
+}
+
+//rotations were separated with the decimal point itself, so "0.11.93" means 0 and 11.93
+//whole part of an angle never has more than 3 digits while printed fractions always do
+fn split_dotted_rotations(number: usize, data: Field) -> Result<Vec<f64>, DefinitionError> {
+    let parts: Vec<Field> = split_columns(data.1, data.0, '.')
+        .into_iter()
+        .filter(|&(_, part)| part.len() != 0)
+        .collect();
+
+    let mut rotations = Vec::new();
+    let mut i = 0;
+
+    while i < parts.len() {
+        if i + 1 < parts.len() && parts[i + 1].1.len() > 3 {
+            let joined = format!("{}.{}", parts[i].1, parts[i + 1].1);
+            rotations.push(parse_field(number, (parts[i].0, &joined), "rotation")?);
+            i += 2;
+        } else {
+            rotations.push(parse_field(number, parts[i], "rotation")?);
+            i += 1;
+        }
+    }
+
+    Ok(rotations)
+}
+
+fn split_columns<'a>(data: &'a str, column: usize, pattern: char) -> Vec<Field<'a>> {
+    let mut result = Vec::new();
+    let mut start = 0;
+
+    for (index, character) in data.char_indices() {
+        if character == pattern {
+            result.push((column + start, &data[start..index]));
+            start = index + 1;
+        }
+    }
+    result.push((column + start, &data[start..]));
+
+    result
+}
+
+//missing fields are reported just past the end of the truncated part
+fn field<'a>(
+    number: usize,
+    line: &str,
+    split: &Vec<Field<'a>>,
+    index: usize,
+    name: &str,
+) -> Result<Field<'a>, DefinitionError> {
+    match split.get(index) {
+        Some(field) => Ok(*field),
+        None => {
+            let last = split[split.len() - 1];
+
+            Err(DefinitionError::new(
+                number,
+                last.0 + last.1.len(),
+                name,
+                format!("Missing {} in: {}", name, line),
+            ))
+        }
+    }
+}
+
+fn parse_field<T: ::std::str::FromStr>(
+    number: usize,
+    data: Field,
+    name: &str,
+) -> Result<T, DefinitionError> {
+    data.1.trim().parse::<T>().map_err(|_| {
+        DefinitionError::new(number, data.0, name, format!("Not a number: {}", data.1))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &'static str = "walk;2;100\n";
+
+    fn error(data: &str) -> (usize, usize, String) {
+        let err = parse(data).unwrap_err();
+
+        (err.line, err.column, err.field)
+    }
+
+    #[test]
+    fn reads_both_doodad_layouts() {
+        let definition =
+            parse("walk;2;100\nhat;0.5;1,2,3/4,5,6.5/\n\nbat;(1,2).(3,4).;0.11.9300\n").unwrap();
+        let clip = &definition.animations[0];
+
+        assert_eq!(clip.spritesheet.durations, vec![100, 100]);
+        assert_eq!(definition.doodads[1].name, "bat");
+        assert_eq!(clip.tracks[0][1].rotation, 6.5);
+        assert_eq!(clip.tracks[0][1].scale, 0.5);
+        assert_eq!(
+            clip.tracks[1]
+                .iter()
+                .map(|frame| frame.rotation)
+                .collect::<Vec<f64>>(),
+            vec![0.0, 11.93]
+        );
+    }
+
+    #[test]
+    fn empty_definition() {
+        assert_eq!(error(""), (1, 1, String::new()));
+    }
+
+    #[test]
+    fn truncated_header() {
+        assert_eq!(error("walk;2\n"), (1, 7, "framerate".to_string()));
+    }
+
+    #[test]
+    fn truncated_doodad() {
+        assert_eq!(
+            error(&format!("{}hat;0.5\n", HEADER)),
+            (2, 8, "positions".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_rotation() {
+        assert_eq!(
+            error(&format!("{}hat;1;3,4,0/5,6/\n", HEADER)),
+            (2, 16, "rotation".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_dotted_rotations() {
+        assert_eq!(
+            error(&format!("{}hat;(1,2).(3,4).;0.\n", HEADER)),
+            (2, 18, "rotations".to_string())
+        );
+    }
+
+    #[test]
+    fn non_numeric_scale() {
+        let err = parse(&format!("{}\nhat;big;1,2,3/\n", HEADER)).unwrap_err();
+
+        assert_eq!((err.line, err.column, err.field.as_str()), (3, 5, "scale"));
+        assert_eq!(err.message, "Not a number: big");
+    }
+
+    #[test]
+    fn non_numeric_position() {
+        assert_eq!(
+            error(&format!("{}hat;1;3,4,0/x,6,0/\n", HEADER)),
+            (2, 13, "x".to_string())
+        );
+    }
+}
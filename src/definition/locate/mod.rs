@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+//maps field paths to the line they start at, paths look like the ones serde_path_to_error gives
+//e.g. animations[0].tracks[1][4].rotation, lookups fall back to the closest parent that has a line
+
+pub struct Lines {
+    lines: HashMap<String, usize>,
+}
+
+impl Lines {
+    pub fn json(data: &str) -> Self {
+        let mut scanner = Scanner {
+            chars: data.chars().peekable(),
+            line: 1,
+            lines: HashMap::new(),
+        };
+        scanner.value(String::new());
+
+        Lines {
+            lines: scanner.lines,
+        }
+    }
+
+    //every lookup gives 0
+    pub fn none() -> Self {
+        Lines {
+            lines: HashMap::new(),
+        }
+    }
+
+    //legacy files keep the header and every doodad on a line of its own,
+    //they are read as a definition with a single animation
+    pub fn legacy(data: &str) -> Self {
+        let mut lines = HashMap::new();
+
+        let numbers = data
+            .lines()
+            .enumerate()
+            .filter(|&(_, line)| line.len() != 0)
+            .map(|(index, _)| index + 1);
+
+        for (index, number) in numbers.enumerate() {
+            match index {
+                0 => lines.insert("animations[0].spritesheet".to_string(), number),
+                _ => {
+                    lines.insert(format!("animations[0].tracks[{}]", index - 1), number);
+                    lines.insert(format!("doodads[{}]", index - 1), number)
+                }
+            };
+        }
+
+        Lines { lines: lines }
+    }
+
+    //0 when nothing on the path has a line
+    pub fn find(&self, path: &str) -> usize {
+        let mut path = path;
+
+        loop {
+            match self.lines.get(path) {
+                Some(line) => return *line,
+                None => {}
+            }
+
+            match path.rfind(|character| character == '.' || character == '[') {
+                Some(index) => path = &path[..index],
+                None => return 0,
+            }
+        }
+    }
+}
+
+//data was already accepted by serde_json, so the scanner only has to find where values start
+struct Scanner<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    lines: HashMap<String, usize>,
+}
+
+impl<'a> Scanner<'a> {
+    fn next(&mut self) -> Option<char> {
+        let character = self.chars.next();
+
+        if character == Some('\n') {
+            self.line += 1;
+        }
+
+        character
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.next();
+        }
+    }
+
+    fn skip_comma(&mut self) {
+        self.skip_whitespace();
+
+        if self.peek() == Some(',') {
+            self.next();
+        }
+    }
+
+    fn value(&mut self, path: String) {
+        self.skip_whitespace();
+        self.lines.insert(path.clone(), self.line);
+
+        match self.peek() {
+            Some('{') => {
+                self.next();
+
+                loop {
+                    self.skip_whitespace();
+
+                    match self.peek() {
+                        Some('}') | None => {
+                            self.next();
+                            break;
+                        }
+                        Some('"') => {
+                            let key = self.string();
+
+                            self.skip_whitespace();
+                            self.next(); //colon
+
+                            let child = match path.len() {
+                                0 => key,
+                                _ => format!("{}.{}", path, key),
+                            };
+                            self.value(child);
+                            self.skip_comma();
+                        }
+                        Some(_) => {
+                            self.next();
+                        }
+                    }
+                }
+            }
+            Some('[') => {
+                self.next();
+
+                let mut index = 0;
+
+                loop {
+                    self.skip_whitespace();
+
+                    match self.peek() {
+                        Some(']') | None => {
+                            self.next();
+                            break;
+                        }
+                        Some(_) => {
+                            self.value(format!("{}[{}]", path, index));
+                            self.skip_comma();
+                            index += 1;
+                        }
+                    }
+                }
+            }
+            Some('"') => {
+                self.string();
+            }
+            _ => {
+                while self.peek().map_or(false, |c| {
+                    c != ',' && c != '}' && c != ']' && !c.is_whitespace()
+                }) {
+                    self.next();
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let mut result = String::new();
+
+        self.next(); //opening quote
+
+        while let Some(character) = self.next() {
+            match character {
+                '\\' => match self.next() {
+                    Some(escaped) => result.push(escaped),
+                    None => break,
+                },
+                '"' => break,
+                character => result.push(character),
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json;
+use serde_json::Value;
+
+use definition::CURRENT_VERSION;
+
+//every step brings a definition one version up, they run in order up to the current version
+pub fn migrate(value: &mut Value, version: u32) {
+    if version < 2 {
+        scale_per_frame(value);
+    }
+    if version < 3 {
+        single_animation(value);
+    }
+    if version < 4 {
+        frame_durations(value);
+    }
+
+    value["version"] = Value::from(CURRENT_VERSION);
+}
+
+//v2: single doodad scale became a part of every frame
+fn scale_per_frame(value: &mut Value) {
+    for doodad in array_mut(value, "doodads") {
+        let scale = doodad.get("scale").cloned().unwrap_or(Value::from(1.0));
+
+        for frame in array_mut(doodad, "frames") {
+            if let Some(frame) = frame.as_object_mut() {
+                frame.insert("scale".to_string(), scale.clone());
+            }
+        }
+
+        if let Some(doodad) = doodad.as_object_mut() {
+            doodad.remove("scale");
+        }
+    }
+}
+
+//v3: the spritesheet and the doodad frames became the first of the named animations,
+//doodads only keep what all animations share
+fn single_animation(value: &mut Value) {
+    let spritesheet = value.get("spritesheet").cloned().unwrap_or(Value::Null);
+    let name = spritesheet.get("name").cloned().unwrap_or(Value::from(""));
+
+    let mut doodads = Vec::new();
+    let mut tracks = Vec::new();
+
+    for doodad in array_mut(value, "doodads") {
+        tracks.push(
+            doodad
+                .get("frames")
+                .cloned()
+                .unwrap_or(Value::Array(Vec::new())),
+        );
+
+        if let Some(doodad) = doodad.as_object_mut() {
+            doodad.remove("frames");
+        }
+        doodads.push(doodad.clone());
+    }
+
+    let mut animation = serde_json::Map::new();
+    animation.insert("name".to_string(), name);
+    animation.insert("spritesheet".to_string(), spritesheet);
+    animation.insert("tracks".to_string(), Value::Array(tracks));
+
+    if let Some(definition) = value.as_object_mut() {
+        definition.remove("spritesheet");
+        definition.insert("doodads".to_string(), Value::Array(doodads));
+        definition.insert(
+            "animations".to_string(),
+            Value::Array(vec![Value::Object(animation)]),
+        );
+    }
+}
+
+//v4: the framerate of a spritesheet became a duration for each of its frames
+fn frame_durations(value: &mut Value) {
+    for animation in array_mut(value, "animations") {
+        let spritesheet = match animation
+            .get_mut("spritesheet")
+            .and_then(Value::as_object_mut)
+        {
+            Some(spritesheet) => spritesheet,
+            None => continue,
+        };
+
+        let frames = spritesheet
+            .get("frames")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        let framerate = spritesheet
+            .remove("framerate")
+            .and_then(|framerate| framerate.as_i64())
+            .unwrap_or(0)
+            .max(0);
+
+        spritesheet.insert(
+            "durations".to_string(),
+            Value::Array((0..frames).map(|_| Value::from(framerate)).collect()),
+        );
+    }
+}
+
+fn array_mut<'a>(value: &'a mut Value, key: &str) -> Vec<&'a mut Value> {
+    match value.get_mut(key).and_then(Value::as_array_mut) {
+        Some(array) => array.iter_mut().collect(),
+        None => Vec::new(),
+    }
+}
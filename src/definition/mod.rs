@@ -0,0 +1,913 @@
+use serde;
+use serde_json;
+use serde_json::Value;
+use serde_path_to_error;
+use std::io;
+use std::path::Path;
+
+use file_utils;
+use hierarchy;
+
+pub mod error;
+pub mod legacy;
+pub mod locate;
+pub mod migration;
+pub use self::error::DefinitionError;
+
+//bump when fields move or change meaning, older versions are migrated on load
+//new fields only need a serde default so older files keep loading
+pub const CURRENT_VERSION: u32 = 4;
+
+//plain data without any sdl handles, so tools can work on definitions without opening a window
+//doodads are shared by every animation, so they are placed once and moved per animation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AnimationDefinition {
+    pub version: u32,
+    pub doodads: Vec<DoodadDefinition>,
+    pub animations: Vec<Clip>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DoodadDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub pivot: Option<Pivot>,
+    //index of the doodad this one is attached to, its frames are relative to that one then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    //higher layers are drawn on top, the spritesheet is between layer -1 and layer 0
+    //doodads on the same layer keep the older rule of the first one being on top
+    #[serde(default)]
+    pub layer: i32,
+    //hidden doodads are neither drawn nor exported, locked ones can not be picked in the editor
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub locked: bool,
+}
+
+//one named animation, there is a track of frames for every doodad in the same order as doodads
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Clip {
+    pub name: String,
+    pub spritesheet: SpritesheetDefinition,
+    pub tracks: Vec<Vec<FrameTransform>>,
+}
+
+//a clip joined with the doodads it moves, what the scene and the exporters work on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub name: String,
+    pub spritesheet: SpritesheetDefinition,
+    pub doodads: Vec<DoodadTrack>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpritesheetDefinition {
+    pub name: String,
+    pub frames: usize,
+    //milliseconds every frame stays on screen, one for each frame
+    pub durations: Vec<u32>,
+    //none slices the sheet by its atlas, or into a single row of equally wide frames
+    #[serde(default)]
+    pub grid: Option<Grid>,
+}
+
+//cells are read left to right, top to bottom, frames may leave the last ones empty
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    //space before the first cell and between neighbouring cells, in pixels
+    #[serde(default)]
+    pub margin: u32,
+    #[serde(default)]
+    pub spacing: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DoodadTrack {
+    pub name: String,
+    pub pivot: Option<Pivot>,
+    pub parent: Option<usize>,
+    pub layer: i32,
+    pub hidden: bool,
+    pub locked: bool,
+    pub frames: Vec<FrameTransform>,
+}
+
+impl DoodadTrack {
+    //new frames start as copies of the first one, the first one counts as where the doodad was put
+    //keys are shown on their own, a frame only keyed is not moved
+    pub fn adjusted(&self) -> Vec<bool> {
+        self.frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                index == 0
+                    || *frame
+                        != FrameTransform {
+                            key: frame.key,
+                            ..self.frames[0].clone()
+                        }
+            })
+            .collect()
+    }
+}
+
+//point of the texture that doodad rotates around, none means the middle of the texture
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Pivot {
+    pub x: f32,
+    pub y: f32,
+}
+
+//offset is relative to the top left corner of the spritesheet frame, for attached doodads see
+//the hierarchy module
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FrameTransform {
+    pub x: i32,
+    pub y: i32,
+    pub rotation: f64,
+    pub scale: f32,
+    #[serde(default)]
+    pub flip_h: bool,
+    #[serde(default)]
+    pub flip_v: bool,
+    #[serde(default = "opaque")]
+    pub alpha: u8,
+    #[serde(default = "no_tint")]
+    pub tint: [u8; 3],
+    //keyframes carry the easing towards the next keyframe, the frames between two keyframes
+    //are tweened and stored like any other frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<Easing>,
+    //layer for this frame only, none uses the layer of the doodad
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<i32>,
+}
+
+//bezier is a css like timing curve from (0, 0) to (1, 1)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    Bezier { x1: f32, y1: f32, x2: f32, y2: f32 },
+}
+
+fn opaque() -> u8 {
+    255
+}
+
+fn no_tint() -> [u8; 3] {
+    [255, 255, 255]
+}
+
+impl AnimationDefinition {
+    //definition with a single animation named after its spritesheet
+    pub fn new(spritesheet: SpritesheetDefinition, doodads: Vec<DoodadTrack>) -> Self {
+        let mut definition = AnimationDefinition {
+            version: CURRENT_VERSION,
+            doodads: Vec::new(),
+            animations: Vec::new(),
+        };
+
+        definition.animations.push(Clip {
+            name: spritesheet.name.clone(),
+            spritesheet: spritesheet.clone(),
+            tracks: Vec::new(),
+        });
+        definition.set_animation(
+            0,
+            Animation {
+                name: spritesheet.name.clone(),
+                spritesheet: spritesheet,
+                doodads: doodads,
+            },
+        );
+
+        definition
+    }
+
+    pub fn animation(&self, index: usize) -> Animation {
+        let clip = &self.animations[index];
+
+        Animation {
+            name: clip.name.clone(),
+            spritesheet: clip.spritesheet.clone(),
+            doodads: self
+                .doodads
+                .iter()
+                .zip(clip.tracks.iter())
+                .map(|(doodad, frames)| DoodadTrack {
+                    name: doodad.name.clone(),
+                    pivot: doodad.pivot.clone(),
+                    parent: doodad.parent,
+                    layer: doodad.layer,
+                    hidden: doodad.hidden,
+                    locked: doodad.locked,
+                    frames: frames.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations.iter().position(|clip| clip.name == name)
+    }
+
+    //doodads added or removed in the animation are added to or removed from the others,
+    //new ones keep their first frame in every frame of the other animations
+    //doodads attached to a different parent do the same, their old frames meant something else
+    pub fn set_animation(&mut self, index: usize, animation: Animation) {
+        let old_keys = doodad_keys(self.doodads.iter().map(|doodad| &doodad.name));
+        let new_keys = doodad_keys(animation.doodads.iter().map(|track| &track.name));
+
+        let old_parents: Vec<Option<&(String, usize)>> = self
+            .doodads
+            .iter()
+            .map(|doodad| doodad.parent.and_then(|parent| old_keys.get(parent)))
+            .collect();
+
+        for (other, clip) in self.animations.iter_mut().enumerate() {
+            if other == index {
+                continue;
+            }
+
+            let frames = clip.spritesheet.frames;
+
+            clip.tracks = new_keys
+                .iter()
+                .zip(animation.doodads.iter())
+                .map(
+                    |(key, track)| match old_keys.iter().position(|old| old == key) {
+                        Some(old)
+                            if old < clip.tracks.len()
+                                && old_parents[old]
+                                    == track.parent.and_then(|parent| new_keys.get(parent)) =>
+                        {
+                            clip.tracks[old].clone()
+                        }
+                        _ => track
+                            .frames
+                            .iter()
+                            .take(1)
+                            .cycle()
+                            .take(frames)
+                            .cloned()
+                            .collect(),
+                    },
+                )
+                .collect();
+        }
+
+        self.doodads = animation
+            .doodads
+            .iter()
+            .map(|track| DoodadDefinition {
+                name: track.name.clone(),
+                pivot: track.pivot.clone(),
+                parent: track.parent,
+                layer: track.layer,
+                hidden: track.hidden,
+                locked: track.locked,
+            })
+            .collect();
+
+        self.animations[index] = Clip {
+            name: animation.name,
+            spritesheet: animation.spritesheet,
+            tracks: animation
+                .doodads
+                .into_iter()
+                .map(|track| track.frames)
+                .collect(),
+        };
+    }
+
+    //doodads start where they are in the first frame of template
+    pub fn add_animation(
+        &mut self,
+        name: String,
+        spritesheet: SpritesheetDefinition,
+        template: usize,
+    ) -> usize {
+        let frames = spritesheet.frames;
+
+        let tracks = self.animations[template]
+            .tracks
+            .iter()
+            .map(|track| track.iter().take(1).cycle().take(frames).cloned().collect())
+            .collect();
+
+        self.animations.push(Clip {
+            name: name,
+            spritesheet: spritesheet,
+            tracks: tracks,
+        });
+
+        self.animations.len() - 1
+    }
+
+    pub fn parse(data: &str) -> Result<Self, DefinitionError> {
+        let value: Value = serde_json::from_str(data).map_err(json_error)?;
+
+        let version = match value.get("version").and_then(Value::as_u64) {
+            Some(version) => version as u32,
+            None => {
+                return Err(DefinitionError::new(
+                    0,
+                    0,
+                    "version",
+                    "Missing definition version".to_string(),
+                ))
+            }
+        };
+
+        if version > CURRENT_VERSION {
+            return Err(DefinitionError::new(
+                0,
+                0,
+                "version",
+                format!(
+                    "Definition version {} is newer than supported {}",
+                    version, CURRENT_VERSION
+                ),
+            ));
+        }
+
+        //migrated definitions lose their positions, current ones are read again to keep them
+        let definition = if version == CURRENT_VERSION {
+            let mut deserializer = serde_json::Deserializer::from_str(data);
+            let definition = deserialize(&mut deserializer)?;
+            deserializer.end().map_err(json_error)?;
+
+            definition
+        } else {
+            let mut value = value;
+            migration::migrate(&mut value, version);
+
+            deserialize(value)?
+        };
+
+        match definition.check() {
+            Ok(()) => Ok(definition),
+            Err(mut err) => {
+                //check works on parsed data, so the line is looked up afterwards
+                err.line = json_lines(data, version).find(&err.field);
+                if err.line != 0 {
+                    err.column = 1;
+                }
+
+                Err(err)
+            }
+        }
+    }
+
+    //rules that the editor relies on and the json schema alone can not express
+    fn check(&self) -> Result<(), DefinitionError> {
+        if self.animations.len() == 0 {
+            return Err(DefinitionError::new(
+                0,
+                0,
+                "animations",
+                "Definition needs at least one animation".to_string(),
+            ));
+        }
+
+        let parents: Vec<Option<usize>> = self.doodads.iter().map(|doodad| doodad.parent).collect();
+
+        for (index, doodad) in self.doodads.iter().enumerate() {
+            match doodad.parent {
+                Some(parent) if parent >= self.doodads.len() => {
+                    return Err(DefinitionError::new(
+                        0,
+                        0,
+                        &format!("doodads[{}].parent", index),
+                        format!(
+                            "Doodad {} is attached to doodad {} but there are {} doodads",
+                            doodad.name,
+                            parent,
+                            self.doodads.len()
+                        ),
+                    ));
+                }
+                Some(_) if hierarchy::descends(&parents, index, index) => {
+                    return Err(DefinitionError::new(
+                        0,
+                        0,
+                        &format!("doodads[{}].parent", index),
+                        format!("Doodad {} ends up attached to itself", doodad.name),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        for (index, clip) in self.animations.iter().enumerate() {
+            let field = format!("animations[{}]", index);
+
+            if clip.spritesheet.frames == 0 {
+                return Err(DefinitionError::new(
+                    0,
+                    0,
+                    &format!("{}.spritesheet.frames", field),
+                    "Spritesheet needs at least one frame".to_string(),
+                ));
+            }
+
+            if clip.spritesheet.durations.len() != clip.spritesheet.frames {
+                return Err(DefinitionError::new(
+                    0,
+                    0,
+                    &format!("{}.spritesheet.durations", field),
+                    format!(
+                        "Spritesheet has {} frames but {} durations",
+                        clip.spritesheet.frames,
+                        clip.spritesheet.durations.len()
+                    ),
+                ));
+            }
+
+            if clip.tracks.len() != self.doodads.len() {
+                return Err(DefinitionError::new(
+                    0,
+                    0,
+                    &format!("{}.tracks", field),
+                    format!(
+                        "Animation {} has {} tracks but there are {} doodads",
+                        clip.name,
+                        clip.tracks.len(),
+                        self.doodads.len()
+                    ),
+                ));
+            }
+
+            for (track, (doodad, frames)) in self.doodads.iter().zip(&clip.tracks).enumerate() {
+                if frames.len() == 0 {
+                    return Err(DefinitionError::new(
+                        0,
+                        0,
+                        &format!("{}.tracks[{}]", field, track),
+                        format!("Doodad {} has no frames in {}", doodad.name, clip.name),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize definition.")
+    }
+}
+
+//the same doodad may be added more than once, so each one is told apart by its occurrence
+fn doodad_keys<'a, I>(names: I) -> Vec<(String, usize)>
+where
+    I: Iterator<Item = &'a String>,
+{
+    let mut keys: Vec<(String, usize)> = Vec::new();
+
+    for name in names {
+        let occurrence = keys.iter().filter(|key| &key.0 == name).count();
+        keys.push((name.clone(), occurrence));
+    }
+
+    keys
+}
+
+fn deserialize<'de, D>(deserializer: D) -> Result<AnimationDefinition, DefinitionError>
+where
+    D: serde::Deserializer<'de, Error = serde_json::Error>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let field = err.path().to_string();
+        let mut err = json_error(err.into_inner());
+        err.field = field;
+
+        err
+    })
+}
+
+fn json_error(err: serde_json::Error) -> DefinitionError {
+    let message = err.to_string();
+
+    //serde_json appends the position which is already a part of the error
+    let message = message.split(" at line ").next().unwrap_or("").to_string();
+
+    DefinitionError::new(err.line(), err.column(), "", message)
+}
+
+pub fn save(name: String, definition: &AnimationDefinition) {
+    file_utils::save_template(name, definition.serialize());
+}
+
+pub fn load(name: String) -> Result<AnimationDefinition, DefinitionError> {
+    match file_utils::find_template(&name) {
+        Some(path) => load_path(&path),
+        None => {
+            Err(DefinitionError::new(0, 0, "", "Definition not found".to_string()).in_file(&name))
+        }
+    }
+}
+
+pub fn autosave(definition: &AnimationDefinition) -> io::Result<()> {
+    file_utils::save_recovery(definition.serialize())
+}
+
+pub fn load_recovery() -> Result<AnimationDefinition, DefinitionError> {
+    load_path(file_utils::recovery_path())
+}
+
+pub fn load_path(path: &Path) -> Result<AnimationDefinition, DefinitionError> {
+    let file = path.to_string_lossy().into_owned();
+
+    let data = match file_utils::load_file(path) {
+        Ok(data) => data,
+        Err(err) => return Err(DefinitionError::new(0, 0, "", err.to_string()).in_file(&file)),
+    };
+
+    parse_source(path, &data).map_err(|err| err.in_file(&file))
+}
+
+//format is picked by the extension of the file the data came from
+pub fn parse_source(path: &Path, data: &str) -> Result<AnimationDefinition, DefinitionError> {
+    if is_legacy(path) {
+        legacy::parse(data)
+    } else {
+        AnimationDefinition::parse(data)
+    }
+}
+
+pub fn source_lines(path: &Path, data: &str) -> locate::Lines {
+    if is_legacy(path) {
+        locate::Lines::legacy(data)
+    } else {
+        let version = serde_json::from_str::<Value>(data)
+            .ok()
+            .and_then(|value| value.get("version").and_then(Value::as_u64))
+            .unwrap_or(0);
+
+        json_lines(data, version as u32)
+    }
+}
+
+//fields are named after the current layout, older files are laid out differently,
+//so their fields are not tied to any line
+fn json_lines(data: &str, version: u32) -> locate::Lines {
+    if version == CURRENT_VERSION {
+        locate::Lines::json(data)
+    } else {
+        locate::Lines::none()
+    }
+}
+
+fn is_legacy(path: &Path) -> bool {
+    path.extension()
+        .map_or(false, |extension| extension == "csv")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn frame(x: i32, y: i32, rotation: f64, scale: f32) -> FrameTransform {
+        FrameTransform {
+            x: x,
+            y: y,
+            rotation: rotation,
+            scale: scale,
+            flip_h: false,
+            flip_v: false,
+            alpha: 255,
+            tint: [255, 255, 255],
+            key: None,
+            layer: None,
+        }
+    }
+
+    fn spritesheet(name: &str, frames: usize) -> SpritesheetDefinition {
+        SpritesheetDefinition {
+            name: name.to_string(),
+            frames: frames,
+            durations: vec![100; frames],
+            grid: None,
+        }
+    }
+
+    fn track(name: &str, parent: Option<usize>, frames: Vec<FrameTransform>) -> DoodadTrack {
+        DoodadTrack {
+            name: name.to_string(),
+            pivot: None,
+            parent: parent,
+            layer: 0,
+            hidden: false,
+            locked: false,
+            frames: frames,
+        }
+    }
+
+    //walk with a hat and a bat, then run made from it
+    fn sample() -> AnimationDefinition {
+        let mut definition = AnimationDefinition::new(
+            spritesheet("walk", 2),
+            vec![
+                track(
+                    "hat",
+                    None,
+                    vec![frame(1, 1, 0.0, 1.0), frame(2, 2, 0.0, 1.0)],
+                ),
+                track(
+                    "bat",
+                    None,
+                    vec![frame(5, 5, 0.0, 1.0), frame(6, 6, 0.0, 1.0)],
+                ),
+            ],
+        );
+        definition.add_animation("run".to_string(), spritesheet("run", 3), 0);
+
+        definition
+    }
+
+    //sample saved with one value replaced, pretty printed so every field has a line of its own
+    fn edited<F>(edit: F) -> String
+    where
+        F: Fn(&mut Value),
+    {
+        let mut value: Value = serde_json::from_str(&sample().serialize()).unwrap();
+        edit(&mut value);
+
+        serde_json::to_string_pretty(&value).unwrap()
+    }
+
+    fn parse_error(data: &str) -> (usize, String, String) {
+        let err = AnimationDefinition::parse(data).unwrap_err();
+
+        (err.line, err.field, err.message)
+    }
+
+    #[test]
+    fn shipped_csv_definitions_survive_a_json_round_trip() {
+        let mut checked = 0;
+
+        for entry in fs::read_dir("resources/definitions").unwrap() {
+            let path = entry.unwrap().path();
+            if !is_legacy(&path) {
+                continue;
+            }
+
+            let definition = load_path(&path).unwrap();
+            let saved = definition.serialize();
+
+            let value: Value = serde_json::from_str(&saved).unwrap();
+            assert_eq!(value["version"], Value::from(CURRENT_VERSION), "{:?}", path);
+            assert_eq!(
+                AnimationDefinition::parse(&saved).unwrap(),
+                definition,
+                "{:?}",
+                path
+            );
+
+            checked += 1;
+        }
+
+        assert!(checked != 0);
+    }
+
+    #[test]
+    fn csv_keeps_the_scale_and_framerate() {
+        let definition =
+            load_path(Path::new("resources/definitions/with_helmet_and_bat.csv")).unwrap();
+        let clip = &definition.animations[0];
+
+        assert_eq!(clip.name, "WalkingManSpriteSheet");
+        assert_eq!(clip.spritesheet.durations, vec![200; 8]);
+        assert_eq!(definition.doodads[0].name, "bat-cutx2");
+        assert_eq!(clip.tracks[0][0], frame(27, 155, 9.260000228881836, 1.0));
+        assert_eq!(clip.tracks[1][0].scale, 0.67700005);
+    }
+
+    #[test]
+    fn version_1_json_is_migrated() {
+        let data = r#"{
+            "version": 1,
+            "spritesheet": { "name": "walk", "frames": 2, "framerate": 120 },
+            "doodads": [
+                {
+                    "name": "hat",
+                    "scale": 0.5,
+                    "frames": [
+                        { "x": 1, "y": 2, "rotation": 3.0 },
+                        { "x": 4, "y": 5, "rotation": 6.0 }
+                    ]
+                }
+            ]
+        }"#;
+
+        let expected = AnimationDefinition {
+            version: CURRENT_VERSION,
+            doodads: vec![DoodadDefinition {
+                name: "hat".to_string(),
+                pivot: None,
+                parent: None,
+                layer: 0,
+                hidden: false,
+                locked: false,
+            }],
+            animations: vec![Clip {
+                name: "walk".to_string(),
+                spritesheet: SpritesheetDefinition {
+                    name: "walk".to_string(),
+                    frames: 2,
+                    durations: vec![120, 120],
+                    grid: None,
+                },
+                tracks: vec![vec![frame(1, 2, 3.0, 0.5), frame(4, 5, 6.0, 0.5)]],
+            }],
+        };
+
+        assert_eq!(AnimationDefinition::parse(data).unwrap(), expected);
+    }
+
+    #[test]
+    fn add_animation_starts_from_the_first_frame_of_the_template() {
+        let definition = sample();
+
+        assert_eq!(definition.find_animation("run"), Some(1));
+        assert_eq!(
+            definition.animations[1].tracks,
+            vec![
+                vec![frame(1, 1, 0.0, 1.0); 3],
+                vec![frame(5, 5, 0.0, 1.0); 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn set_animation_adds_and_removes_doodads_everywhere() {
+        let mut definition = sample();
+
+        let mut walk = definition.animation(0);
+        walk.doodads.remove(0);
+        walk.doodads.push(track(
+            "axe",
+            None,
+            vec![frame(9, 9, 0.0, 1.0), frame(8, 8, 0.0, 1.0)],
+        ));
+        definition.set_animation(0, walk);
+
+        let names: Vec<&str> = definition
+            .doodads
+            .iter()
+            .map(|doodad| doodad.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["bat", "axe"]);
+
+        //bat keeps its frames of run, the new axe stays where it is in its first frame
+        assert_eq!(
+            definition.animations[1].tracks,
+            vec![
+                vec![frame(5, 5, 0.0, 1.0); 3],
+                vec![frame(9, 9, 0.0, 1.0); 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn set_animation_resets_other_animations_of_reattached_doodads() {
+        let mut definition = sample();
+        let mut moved = definition.animation(1);
+        moved.doodads[0].frames[2] = frame(3, 3, 0.0, 1.0);
+        definition.set_animation(1, moved);
+
+        let mut walk = definition.animation(0);
+        walk.doodads[1].frames[1] = frame(7, 7, 0.0, 1.0);
+        walk.doodads[1].parent = Some(0);
+        definition.set_animation(0, walk);
+
+        assert_eq!(definition.doodads[1].parent, Some(0));
+        assert_eq!(definition.animations[1].tracks[0][2], frame(3, 3, 0.0, 1.0));
+        assert_eq!(
+            definition.animations[1].tracks[1],
+            vec![frame(5, 5, 0.0, 1.0); 3]
+        );
+    }
+
+    #[test]
+    fn parse_needs_a_known_version() {
+        let missing = edited(|value| {
+            value.as_object_mut().unwrap().remove("version");
+        });
+        assert_eq!(
+            parse_error(&missing),
+            (
+                0,
+                "version".to_string(),
+                "Missing definition version".to_string()
+            )
+        );
+
+        let newer = edited(|value| value["version"] = Value::from(CURRENT_VERSION + 1));
+        assert_eq!(parse_error(&newer).1, "version");
+    }
+
+    #[test]
+    fn parse_reports_syntax_and_type_errors() {
+        let err =
+            AnimationDefinition::parse("{\n  \"version\": 4,\n  \"doodads\": [\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (4, 1));
+
+        let data =
+            edited(|value| value["animations"][1]["spritesheet"]["frames"] = Value::from("3"));
+        assert_eq!(parse_error(&data).1, "animations[1].spritesheet.frames");
+    }
+
+    #[test]
+    fn parse_rejects_trailing_characters() {
+        let data = sample().serialize() + "\n}";
+        let lines = data.lines().count();
+
+        let err = AnimationDefinition::parse(&data).unwrap_err();
+        assert_eq!(
+            (err.line, err.message),
+            (lines, "trailing characters".to_string())
+        );
+    }
+
+    #[test]
+    fn check_rejects_mismatched_durations() {
+        let data = edited(|value| {
+            value["animations"][1]["spritesheet"]["durations"] = Value::from(vec![100, 100])
+        });
+        let err = AnimationDefinition::parse(&data).unwrap_err();
+
+        assert_eq!(err.field, "animations[1].spritesheet.durations");
+        assert_eq!(err.message, "Spritesheet has 3 frames but 2 durations");
+        assert_eq!(
+            data.lines().nth(err.line - 1).unwrap().trim(),
+            "\"durations\": ["
+        );
+        assert_eq!(err.column, 1);
+    }
+
+    #[test]
+    fn check_rejects_missing_tracks_and_frames() {
+        let data = edited(|value| {
+            value["animations"][0]["tracks"]
+                .as_array_mut()
+                .unwrap()
+                .pop();
+        });
+        assert_eq!(parse_error(&data).1, "animations[0].tracks");
+
+        let data = edited(|value| value["animations"][1]["tracks"][1] = Value::Array(Vec::new()));
+        assert_eq!(parse_error(&data).1, "animations[1].tracks[1]");
+
+        let data = edited(|value| value["animations"] = Value::Array(Vec::new()));
+        assert_eq!(parse_error(&data).1, "animations");
+    }
+
+    #[test]
+    fn check_errors_of_migrated_definitions_have_no_line() {
+        let data = r#"{
+            "version": 3,
+            "doodads": [{ "name": "hat", "parent": 1 }],
+            "animations": [
+                {
+                    "name": "walk",
+                    "spritesheet": { "name": "walk", "frames": 1, "framerate": 100 },
+                    "tracks": [[{ "x": 0, "y": 0, "rotation": 0.0, "scale": 1.0 }]]
+                }
+            ]
+        }"#;
+        let err = AnimationDefinition::parse(data).unwrap_err();
+
+        assert_eq!((err.line, err.column), (0, 0));
+        assert_eq!(err.field, "doodads[0].parent");
+    }
+
+    #[test]
+    fn check_rejects_broken_parents() {
+        let data = edited(|value| value["doodads"][0]["parent"] = Value::from(2));
+        assert_eq!(
+            parse_error(&data).2,
+            "Doodad hat is attached to doodad 2 but there are 2 doodads"
+        );
+
+        let data = edited(|value| {
+            value["doodads"][0]["parent"] = Value::from(1);
+            value["doodads"][1]["parent"] = Value::from(0);
+        });
+        let (_, field, message) = parse_error(&data);
+        assert_eq!(field, "doodads[0].parent");
+        assert_eq!(message, "Doodad hat ends up attached to itself");
+    }
+}
@@ -0,0 +1,312 @@
+use serde_json;
+use std::cmp::Reverse;
+use std::f64::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use atlas;
+use definition::{Animation, AnimationDefinition, FrameTransform};
+use file_utils;
+use hierarchy::{self, Placement};
+
+//attachment data for game engines, every value is already resolved per frame
+//
+//{
+//  "schema": 3,
+//  "animations": [
+//    {
+//      "name": "walk",
+//      "spritesheet": "knight",      name of the png in resources/spritesheets
+//      "frame_width": 64,            size of a single frame in pixels, the largest one for atlases
+//      "frame_height": 64,
+//      "frames": [
+//        {
+//          "index": 0,
+//          "duration_ms": 100,       how long the frame stays on screen
+//          "attachments": [
+//            {
+//              "name": "helmet",     name of the png in resources/doodads
+//              "width": 32,          size of the doodad texture in pixels, before scaling
+//              "height": 16,
+//              "x": 12,              top left corner relative to the top left corner of the frame
+//              "y": -4,
+//              "scale": 1.5,         the texture is scaled from its top left corner
+//              "pivot_x": 16.0,      point the doodad rotates around, in unscaled texture pixels
+//              "pivot_y": 8.0,       of the texture before flipping
+//              "rotation_degrees": 90.0,   clockwise
+//              "rotation_radians": 1.5707963267948966,
+//              "flip_h": false,
+//              "flip_v": false,
+//              "alpha": 255,
+//              "tint": [255, 255, 255],
+//              "draw_order": 1       the spritesheet is 0, higher values are drawn on top and
+//                                    negative ones behind the spritesheet
+//            }
+//          ]
+//        }
+//      ]
+//    }
+//  ]
+//}
+//
+//attachments keep the order of the definition, hidden doodads are left out
+//doodads attached to another doodad are placed through their parents, so they look the same
+
+//bump when fields move or change meaning, engines should refuse schemas they do not know
+//2: animations of a definition are exported together
+//3: draw order follows the layers and goes below 0 for doodads behind the spritesheet
+pub const SCHEMA_VERSION: u32 = 3;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EngineExport {
+    pub schema: u32,
+    pub animations: Vec<EngineAnimation>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EngineAnimation {
+    pub name: String,
+    pub spritesheet: String,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub frames: Vec<EngineFrame>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct EngineFrame {
+    pub index: usize,
+    pub duration_ms: u32,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub scale: f32,
+    pub pivot_x: f32,
+    pub pivot_y: f32,
+    pub rotation_degrees: f64,
+    pub rotation_radians: f64,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub alpha: u8,
+    pub tint: [u8; 3],
+    pub draw_order: i32,
+}
+
+//texture sizes come from the png headers, missing textures fail the whole export
+pub fn convert(definition: &Animation) -> Result<EngineAnimation, String> {
+    let name = &definition.spritesheet.name;
+    let frame_count = definition.spritesheet.frames;
+
+    let atlas = atlas::resolve(&definition.spritesheet)
+        .map_err(|message| format!("Spritesheet {} can not be loaded: {}", name, message))?;
+
+    let mut sizes = Vec::new();
+    for track in &definition.doodads {
+        sizes.push(
+            file_utils::image_size(&file_utils::doodad_path(&track.name)).map_err(|message| {
+                format!("Doodad {} can not be loaded: {}", track.name, message)
+            })?,
+        );
+    }
+
+    let pivots: Vec<(f32, f32)> = definition
+        .doodads
+        .iter()
+        .zip(sizes.iter())
+        .map(|(track, &(width, height))| match track.pivot {
+            Some(ref pivot) => (pivot.x, pivot.y),
+            None => (width as f32 / 2.0, height as f32 / 2.0),
+        })
+        .collect();
+    let parents = definition
+        .doodads
+        .iter()
+        .map(|track| track.parent)
+        .collect();
+
+    let mut frames = Vec::new();
+
+    for index in 0..frame_count {
+        let mut attachments = Vec::new();
+
+        let placements = hierarchy::resolve(&parents, |order| {
+            let track = &definition.doodads[order];
+
+            match frame_at(&track.frames, index) {
+                Some(frame) => match track.parent {
+                    Some(_) => Placement {
+                        x: frame.x as f32,
+                        y: frame.y as f32,
+                        rotation: frame.rotation,
+                        scale: frame.scale,
+                    },
+                    None => hierarchy::from_corner(
+                        frame.x,
+                        frame.y,
+                        center(pivots[order], sizes[order], frame),
+                        frame.rotation,
+                        frame.scale,
+                    ),
+                },
+                None => Placement {
+                    x: 0.0,
+                    y: 0.0,
+                    rotation: 0.0,
+                    scale: 1.0,
+                },
+            }
+        });
+
+        let draw_orders = draw_orders(&definition, index);
+
+        for (order, (track, &(doodad_width, doodad_height))) in
+            definition.doodads.iter().zip(sizes.iter()).enumerate()
+        {
+            let frame = match frame_at(&track.frames, index) {
+                Some(frame) if !track.hidden => frame,
+                _ => continue,
+            };
+
+            let (pivot_x, pivot_y) = pivots[order];
+
+            let (x, y, rotation, scale) = match track.parent {
+                Some(_) => {
+                    let placement = &placements[order];
+                    let (x, y) =
+                        hierarchy::corner(placement, center(pivots[order], sizes[order], frame));
+
+                    (x, y, placement.rotation, placement.scale)
+                }
+                None => (frame.x, frame.y, frame.rotation, frame.scale),
+            };
+
+            attachments.push(Attachment {
+                name: track.name.clone(),
+                width: doodad_width,
+                height: doodad_height,
+                x: x,
+                y: y,
+                scale: scale,
+                pivot_x: pivot_x,
+                pivot_y: pivot_y,
+                rotation_degrees: rotation,
+                rotation_radians: rotation * PI / 180.0,
+                flip_h: frame.flip_h,
+                flip_v: frame.flip_v,
+                alpha: frame.alpha,
+                tint: frame.tint,
+                draw_order: draw_orders[order],
+            });
+        }
+
+        frames.push(EngineFrame {
+            index: index,
+            duration_ms: definition.spritesheet.durations[index],
+            attachments: attachments,
+        });
+    }
+
+    Ok(EngineAnimation {
+        name: definition.name.clone(),
+        spritesheet: name.clone(),
+        frame_width: atlas.frame_width,
+        frame_height: atlas.frame_height,
+        frames: frames,
+    })
+}
+
+//same wrap around the editor does for tracks shorter than the spritesheet
+fn frame_at(frames: &Vec<FrameTransform>, index: usize) -> Option<&FrameTransform> {
+    match frames.len() {
+        0 => None,
+        len => Some(&frames[index % len]),
+    }
+}
+
+//same stacking the editor draws with, counted away from the spritesheet in both directions
+fn draw_orders(definition: &Animation, index: usize) -> Vec<i32> {
+    let layers: Vec<i32> = definition
+        .doodads
+        .iter()
+        .map(|track| match frame_at(&track.frames, index) {
+            Some(frame) => frame.layer.unwrap_or(track.layer),
+            None => track.layer,
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..layers.len()).collect();
+    order.sort_by_key(|&order| (layers[order], Reverse(order)));
+
+    let behind = layers.iter().filter(|&&layer| layer < 0).count() as i32;
+
+    let mut draw_orders = vec![0; layers.len()];
+    for (position, &order) in order.iter().enumerate() {
+        let position = position as i32;
+
+        draw_orders[order] = match position < behind {
+            true => position - behind,
+            false => position - behind + 1,
+        };
+    }
+
+    draw_orders
+}
+
+//pivot with the flips of the frame applied, what the doodad is rotated around
+fn center(pivot: (f32, f32), size: (u32, u32), frame: &FrameTransform) -> (f32, f32) {
+    (
+        match frame.flip_h {
+            true => size.0 as f32 - pivot.0,
+            false => pivot.0,
+        },
+        match frame.flip_v {
+            true => size.1 as f32 - pivot.1,
+            false => pivot.1,
+        },
+    )
+}
+
+pub fn export_engine(definition: &AnimationDefinition, output: &Path) -> Result<(), String> {
+    fs::write(output, engine_json(definition)?).map_err(|err| err.to_string())
+}
+
+fn engine_json(definition: &AnimationDefinition) -> Result<String, String> {
+    let mut animations = Vec::new();
+    for index in 0..definition.animations.len() {
+        animations.push(convert(&definition.animation(index))?);
+    }
+
+    let export = EngineExport {
+        schema: SCHEMA_VERSION,
+        animations: animations,
+    };
+
+    let data = serde_json::to_string_pretty(&export).map_err(|err| err.to_string())?;
+
+    Ok(data + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //covers attached, flipped, pivoted, hidden and layered doodads in two animations
+    //when the output changes on purpose, bump SCHEMA_VERSION if engines read it differently
+    //and replace golden/export.json with what the test printed
+    #[test]
+    fn matches_the_golden_file() {
+        let definition =
+            AnimationDefinition::parse(include_str!("golden/definition.json")).unwrap();
+        let data = engine_json(&definition).unwrap();
+
+        if data != include_str!("golden/export.json") {
+            panic!("engine export differs from golden/export.json:\n{}", data);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use gif;
+use png;
+use sdl2::image::SaveSurface;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Canvas};
+use sdl2::surface::{Surface, SurfaceRef};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use atlas::{self, Atlas};
+use definition::Animation;
+use fragment::Fragment;
+use resource_manager::ResourceManager;
+use scene;
+
+pub mod engine;
+
+//fragments at position 0 are nudged by a pixel when drawn, so frames are rendered one pixel in
+const ORIGIN: i32 = 1;
+
+#[derive(Clone, Debug)]
+pub struct AnimationExport {
+    pub scale: f32,
+    //none keeps the frames transparent
+    pub background: Option<Color>,
+    //times the animation plays, 0 loops forever
+    pub loops: u16,
+}
+
+fn atlas(definition: &Animation) -> Result<Atlas, String> {
+    atlas::resolve(&definition.spritesheet)
+}
+
+fn frame_size(definition: &Animation) -> Result<(u32, u32), String> {
+    let atlas = atlas(definition)?;
+
+    Ok((atlas.frame_width, atlas.frame_height))
+}
+
+//renders every frame with the software renderer, the frame sits at frame_rect of the surface
+fn render_frames<F>(definition: &Animation, mut visit: F) -> Result<(), String>
+where
+    F: FnMut(usize, &mut SurfaceRef) -> Result<(), String>,
+{
+    let (frame_width, height) = frame_size(definition)?;
+
+    //doodads reaching past the frame are cut off by the scratch canvas
+    let scratch = Surface::new(
+        frame_width + ORIGIN as u32,
+        height + ORIGIN as u32,
+        PixelFormatEnum::RGBA32,
+    )?;
+    let mut canvas = Canvas::from_surface(scratch)?;
+    let texture_creator = canvas.texture_creator();
+    let mut manager = ResourceManager::new(&texture_creator);
+
+    let (mut spritesheet, mut doodads) = scene::build(&mut manager, definition, ORIGIN, ORIGIN)?;
+
+    for frame in 0..definition.spritesheet.frames {
+        spritesheet.set_frame(frame);
+        for doodad in &mut doodads {
+            doodad.set_frame(frame);
+        }
+
+        canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+        canvas.clear();
+
+        scene::draw(&mut canvas, &spritesheet, &doodads)?;
+        canvas.present();
+
+        visit(frame, canvas.surface_mut())?;
+    }
+
+    Ok(())
+}
+
+fn frame_rect(width: u32, height: u32) -> Rect {
+    Rect::new(ORIGIN, ORIGIN, width, height)
+}
+
+//writes them as one horizontal strip, the same layout Spritesheet::new slices,
+//so the result loads back as a spritesheet
+pub fn bake_spritesheet(definition: &Animation, output: &Path) -> Result<(), String> {
+    if atlas::has_sidecar(output) {
+        return Err(format!(
+            "{} has an atlas next to it, bake to another name",
+            output.display()
+        ));
+    }
+
+    let (frame_width, height) = frame_size(definition)?;
+    let frames = definition.spritesheet.frames as u32;
+
+    let mut strip = Surface::new(frame_width * frames, height, PixelFormatEnum::RGBA32)?;
+
+    render_frames(definition, |frame, surface| {
+        //copied as is, blending would darken the transparent edges
+        surface.set_blend_mode(BlendMode::None)?;
+        surface.blit(
+            frame_rect(frame_width, height),
+            &mut strip,
+            Rect::new(frame as i32 * frame_width as i32, 0, frame_width, height),
+        )?;
+
+        Ok(())
+    })?;
+
+    strip.save(output)
+}
+
+//frames scaled and put on the background, as tightly packed rgba rows
+fn render_animation(
+    definition: &Animation,
+    options: &AnimationExport,
+) -> Result<(u32, u32, Vec<Vec<u8>>), String> {
+    let (frame_width, height) = frame_size(definition)?;
+
+    let width = ((frame_width as f32 * options.scale).round() as u32).max(1);
+    let height_scaled = ((height as f32 * options.scale).round() as u32).max(1);
+
+    let mut frames = Vec::new();
+
+    render_frames(definition, |_, surface| {
+        let mut target = Surface::new(width, height_scaled, PixelFormatEnum::RGBA32)?;
+
+        match options.background {
+            Some(color) => {
+                target.fill_rect(None, color)?;
+                surface.set_blend_mode(BlendMode::Blend)?;
+            }
+            None => surface.set_blend_mode(BlendMode::None)?,
+        }
+
+        surface.blit_scaled(frame_rect(frame_width, height), &mut target, None)?;
+
+        frames.push(packed_pixels(&target));
+
+        Ok(())
+    })?;
+
+    Ok((width, height_scaled, frames))
+}
+
+//surface rows may be padded, encoders expect them back to back
+fn packed_pixels(surface: &SurfaceRef) -> Vec<u8> {
+    let row = surface.width() as usize * 4;
+    let pitch = surface.pitch() as usize;
+
+    surface.with_lock(|pixels| {
+        pixels
+            .chunks(pitch)
+            .take(surface.height() as usize)
+            .flat_map(|line| line[..row].iter().cloned())
+            .collect()
+    })
+}
+
+//one png per frame, named by the frame number, only the cli offers it for now
+#[allow(dead_code)]
+pub fn export_frames(
+    definition: &Animation,
+    options: &AnimationExport,
+    directory: &Path,
+) -> Result<Vec<PathBuf>, String> {
+    let (width, height, frames) = render_animation(definition, options)?;
+
+    let mut written = Vec::new();
+
+    for (index, pixels) in frames.iter().enumerate() {
+        let path = directory.join(format!("{}.png", index));
+
+        let file = File::create(&path).map_err(|err| err.to_string())?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+        writer
+            .write_image_data(pixels)
+            .map_err(|err| err.to_string())?;
+
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+pub fn export_gif(
+    definition: &Animation,
+    options: &AnimationExport,
+    output: &Path,
+) -> Result<(), String> {
+    let (width, height, frames) = render_animation(definition, options)?;
+    let (width, height) = gif_size(width, height)?;
+
+    let file = File::create(output).map_err(|err| err.to_string())?;
+    let mut encoder = gif::Encoder::new(BufWriter::new(file), width, height, &[])
+        .map_err(|err| err.to_string())?;
+
+    let repeat = match options.loops {
+        0 => gif::Repeat::Infinite,
+        loops => gif::Repeat::Finite(loops - 1),
+    };
+    encoder.set_repeat(repeat).map_err(|err| err.to_string())?;
+
+    for (index, mut pixels) in frames.into_iter().enumerate() {
+        let mut frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        //gif delays are in hundredths of a second
+        frame.delay = (definition.spritesheet.durations[index] / 10).min(0xffff) as u16;
+        frame.dispose = gif::DisposalMethod::Background;
+
+        encoder.write_frame(&frame).map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+//gifs store their size in 16 bits, larger frames would wrap around
+fn gif_size(width: u32, height: u32) -> Result<(u16, u16), String> {
+    match width <= u16::max_value() as u32 && height <= u16::max_value() as u32 {
+        true => Ok((width as u16, height as u16)),
+        false => Err(format!(
+            "{}x{} is too large for a gif, frames can be at most {} pixels on each side",
+            width,
+            height,
+            u16::max_value()
+        )),
+    }
+}
+
+pub fn export_apng(
+    definition: &Animation,
+    options: &AnimationExport,
+    output: &Path,
+) -> Result<(), String> {
+    let (width, height, frames) = render_animation(definition, options)?;
+
+    let file = File::create(output).map_err(|err| err.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .set_animated(frames.len() as u32, options.loops as u32)
+        .map_err(|err| err.to_string())?;
+    encoder
+        .set_dispose_op(png::DisposeOp::Background)
+        .map_err(|err| err.to_string())?;
+
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+
+    for (index, pixels) in frames.into_iter().enumerate() {
+        //durations are in milliseconds
+        let delay = definition.spritesheet.durations[index];
+        writer
+            .set_frame_delay(delay.min(0xffff) as u16, 1000)
+            .map_err(|err| err.to_string())?;
+        writer
+            .write_image_data(&pixels)
+            .map_err(|err| err.to_string())?;
+    }
+
+    writer.finish().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gif_size_fits_in_16_bits() {
+        assert_eq!(gif_size(320, 65535), Ok((320, 65535)));
+        assert_eq!(
+            gif_size(65536, 10),
+            Err(
+                "65536x10 is too large for a gif, frames can be at most 65535 pixels on each side"
+                    .to_string()
+            )
+        );
+        assert!(gif_size(10, 70000).is_err());
+    }
+}
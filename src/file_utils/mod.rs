@@ -1,5 +1,15 @@
+use png;
 use std::fs;
-use std::path::Path;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const DEFINITIONS_PATH: &'static str = "resources/definitions/";
+const SPRITESHEETS_PATH: &'static str = "resources/spritesheets/";
+const DOODADS_PATH: &'static str = "resources/doodads/";
+const EXPORTS_PATH: &'static str = "exports/";
+//only removed on a clean exit, finding it on startup means the last session was lost
+const RECOVERY_PATH: &'static str = "usr/recovery.json";
 
 fn list_directory(path: &'static str) -> Vec<String> {
     let paths = fs::read_dir(path).unwrap();
@@ -7,9 +17,12 @@ fn list_directory(path: &'static str) -> Vec<String> {
     let mut vec: Vec<String> = Vec::new();
 
     for path in paths {
-        let temp = path.unwrap().file_name().into_string().unwrap();
+        let path = path.unwrap().path();
 
-        vec.push(temp.chars().take(temp.len() - 4).collect());
+        match path.file_stem() {
+            Some(stem) => vec.push(stem.to_string_lossy().into_owned()),
+            None => {}
+        }
     }
 
     vec
@@ -23,33 +36,79 @@ pub fn get_imgui_directory(path: &'static str) -> Vec<imgui::ImString> {
     imgui_list_directory(list_directory(path))
 }
 
-pub fn save_template(name: String, data: String) {
-    let mut path = String::from("resources/definitions/");
-    path += &name;
-    path += ".csv";
+fn template_path(name: &str, extension: &str) -> PathBuf {
+    let mut path = String::from(DEFINITIONS_PATH);
+    path += name;
+    path += ".";
+    path += extension;
+
+    PathBuf::from(path)
+}
+
+pub fn spritesheet_path(name: &str) -> PathBuf {
+    PathBuf::from(String::from(SPRITESHEETS_PATH) + name + ".png")
+}
+
+//texturepacker or aseprite json describing the frames of the spritesheet of the same name
+pub fn atlas_path(name: &str) -> PathBuf {
+    PathBuf::from(String::from(SPRITESHEETS_PATH) + name + ".json")
+}
+
+//exports are not read back by the editor, so they are kept out of resources
+pub fn export_path(name: &str, extension: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(EXPORTS_PATH)?;
+
+    Ok(PathBuf::from(
+        String::from(EXPORTS_PATH) + name + "." + extension,
+    ))
+}
 
-    let path = Path::new(&path);
+pub fn doodad_path(name: &str) -> PathBuf {
+    PathBuf::from(String::from(DOODADS_PATH) + name + ".png")
+}
+
+//width and height from the png header, the pixels are not decoded
+pub fn image_size(path: &Path) -> Result<(u32, u32), String> {
+    let file = File::open(path).map_err(|err| err.to_string())?;
+    let reader = png::Decoder::new(file)
+        .read_info()
+        .map_err(|err| err.to_string())?;
+
+    Ok((reader.info().width, reader.info().height))
+}
+
+pub fn save_template(name: String, data: String) {
+    let path = template_path(&name, "json");
 
     fs::write(path, data).expect("Failed to initialise file write.");
 }
 
-pub fn load_file_by_lines(name: String) -> Vec<String> {
-    let mut path = String::from("resources/definitions/");
-    path += &name;
-    path += ".csv";
+//json definitions take precedence over the legacy csv ones
+pub fn find_template(name: &str) -> Option<PathBuf> {
+    ["json", "csv"]
+        .iter()
+        .map(|extension| template_path(name, extension))
+        .find(|path| path.exists())
+}
 
-    let path = Path::new(&path);
+pub fn load_file(path: &Path) -> io::Result<String> {
+    fs::read_to_string(path)
+}
 
-    let buffer = fs::read_to_string(path).expect("Failed to initialise file read.");
+pub fn recovery_path() -> &'static Path {
+    Path::new(RECOVERY_PATH)
+}
 
-    let mut result = Vec::new();
-    for line in buffer.lines() {
-        result.push(line.to_owned());
-    }
+//written next to the target and renamed, a crash in the middle keeps the previous autosave
+pub fn save_recovery(data: String) -> io::Result<()> {
+    let temp = recovery_path().with_extension("tmp");
 
-    result
+    fs::write(&temp, data)?;
+    fs::rename(&temp, recovery_path())
 }
 
-pub fn split_line(data: &String, pattern: &str) -> Vec<String> {
-    data.split(pattern).map(String::from).collect()
+pub fn remove_recovery() {
+    if recovery_path().exists() {
+        fs::remove_file(recovery_path()).expect("Failed to remove recovery file.");
+    }
 }
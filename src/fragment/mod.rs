@@ -1,15 +1,24 @@
+use sdl2::pixels::Color;
 use sdl2::render::Texture;
 use std::rc::Rc;
 
+use atlas::{Atlas, AtlasFrame};
+use definition::{DoodadTrack, Easing, FrameTransform, Grid, Pivot, SpritesheetDefinition};
+use hierarchy::{self, Placement};
+use tween;
+
 pub struct Spritesheet<'a> {
     name: String,
     texture: Rc<Texture<'a>>,
     source_rect: sdl2::rect::Rect,
-    frame_width: i32,
+    frames: Vec<AtlasFrame>,
+    grid: Option<Grid>,
     position: sdl2::rect::Rect,
 
     scale: f32,
     rotation: f64,
+    flip: (bool, bool),
+    color: Color,
 
     frame_count: usize,
 
@@ -24,19 +33,37 @@ impl<'a> Spritesheet<'a> {
         y_pos: i32,
         frame_count: usize,
     ) -> Self {
-        let width = texture.query().width / frame_count as u32;
-        let height = texture.query().height;
+        let atlas = Atlas::uniform(texture.query().width, texture.query().height, frame_count);
+
+        Spritesheet::from_atlas(name, texture, x_pos, y_pos, atlas, None)
+    }
+
+    //position is the frame the atlas describes, trimmed frames are drawn inside of it
+    //grid is only kept so the definition can record how the sheet was cut
+    pub fn from_atlas(
+        name: String,
+        texture: Rc<Texture<'a>>,
+        x_pos: i32,
+        y_pos: i32,
+        atlas: Atlas,
+        grid: Option<Grid>,
+    ) -> Self {
+        let frame_count = atlas.frames.len();
+        let first = atlas.frames[0].clone();
 
         Spritesheet {
             name: name,
             texture: texture,
-            source_rect: sdl2::rect::Rect::new(0, 0, width, height),
-            frame_width: width as i32,
+            source_rect: sdl2::rect::Rect::new(first.x, first.y, first.width, first.height),
+            frames: atlas.frames,
+            grid: grid,
 
-            position: sdl2::rect::Rect::new(x_pos, y_pos, width, height),
+            position: sdl2::rect::Rect::new(x_pos, y_pos, atlas.frame_width, atlas.frame_height),
 
             scale: 1.0,
             rotation: 0.0,
+            flip: (false, false),
+            color: Color::RGBA(255, 255, 255, 255),
 
             frame_count: frame_count,
 
@@ -45,34 +72,64 @@ impl<'a> Spritesheet<'a> {
     }
 
     fn update_frame(&mut self) {
-        self.source_rect
-            .set_x(self.current as i32 * self.frame_width);
-    }
+        let frame = &self.frames[self.current];
 
-    pub fn serialize(&self) -> String {
-        let mut temp = String::new();
-        temp += &self.name;
-        temp += ";";
-
-        temp += &self.frame_count.to_string();
-        temp += ";"; //fetch framerate
+        self.source_rect = sdl2::rect::Rect::new(frame.x, frame.y, frame.width, frame.height);
+    }
 
-        temp
+    pub fn to_definition(&self, durations: Vec<u32>) -> SpritesheetDefinition {
+        SpritesheetDefinition {
+            name: self.name.clone(),
+            frames: self.frame_count,
+            durations: durations,
+            grid: self.grid,
+        }
     }
 
     pub fn get_frames_amount(&self) -> usize {
         self.frame_count
     }
+
+    //part of the texture any frame is cut from, not only the current one
+    pub fn get_frame_rect(&self, frame: usize) -> Option<sdl2::rect::Rect> {
+        self.frames
+            .get(frame)
+            .map(|frame| sdl2::rect::Rect::new(frame.x, frame.y, frame.width, frame.height))
+    }
+
+    //durations the atlas asks for, fallback for frames it says nothing about
+    pub fn get_durations(&self, fallback: u32) -> Vec<u32> {
+        self.frames
+            .iter()
+            .map(|frame| frame.duration.unwrap_or(fallback))
+            .collect()
+    }
 }
 
+//attached doodads keep their frames relative to the parent, like the definition does
+//positions hold the offset of the pivot then, and everything read from the outside is composed
 pub struct Doodad<'a> {
     name: String,
     texture: Rc<Texture<'a>>,
     source_rect: sdl2::rect::Rect,
     positions: Vec<sdl2::rect::Rect>,
-
-    scale: f32,
+    pivot: (f32, f32),
+    parent: Option<usize>,
+    //where the parent is in every frame, filled in by the scene
+    joints: Vec<Placement>,
+    //moves smaller than a pixel of the parent, carried over to the next one
+    drift: (f32, f32),
+    layer: i32,
+    hidden: bool,
+    locked: bool,
+
+    scales: Vec<f32>,
     rotations: Vec<f64>,
+    flips: Vec<(bool, bool)>,
+    colors: Vec<Color>,
+    keys: Vec<Option<Easing>>,
+    //frames on a layer of their own
+    layers: Vec<Option<i32>>,
 
     current: usize,
 }
@@ -89,24 +146,50 @@ impl<'a> Doodad<'a> {
         let heigth = texture.query().height;
 
         let mut positions: Vec<sdl2::rect::Rect> = Vec::new();
+        let mut scales: Vec<f32> = Vec::new();
         let mut rotations: Vec<f64> = Vec::new();
+        let mut flips: Vec<(bool, bool)> = Vec::new();
+        let mut colors: Vec<Color> = Vec::new();
 
         for _ in 0..frame_count {
             positions.push(sdl2::rect::Rect::new(x_pos, y_pos, width, heigth));
         }
 
+        for _ in 0..frame_count {
+            scales.push(1.0);
+        }
+
         for _ in 0..frame_count {
             rotations.push(0.0);
         }
 
+        for _ in 0..frame_count {
+            flips.push((false, false));
+        }
+
+        for _ in 0..frame_count {
+            colors.push(Color::RGBA(255, 255, 255, 255));
+        }
+
         Doodad {
             name: name,
             texture: texture,
             source_rect: sdl2::rect::Rect::new(0, 0, width, heigth),
             positions: positions,
-
-            scale: 1.0,
+            pivot: (width as f32 / 2.0, heigth as f32 / 2.0),
+            parent: None,
+            joints: Vec::new(),
+            drift: (0.0, 0.0),
+            layer: 0,
+            hidden: false,
+            locked: false,
+
+            scales: scales,
             rotations: rotations,
+            flips: flips,
+            colors: colors,
+            keys: vec![None; frame_count as usize],
+            layers: vec![None; frame_count as usize],
 
             current: 0,
         }
@@ -114,63 +197,437 @@ impl<'a> Doodad<'a> {
 
     pub fn set_frames_amount(&mut self, frames: usize) {
         let cloned_first_position = self.positions.first().cloned().unwrap();
+        let cloned_first_scale = self.scales.first().cloned().unwrap();
         let cloned_first_rotation = self.rotations.first().cloned().unwrap();
+        let cloned_first_flip = self.flips.first().cloned().unwrap();
+        let cloned_first_color = self.colors.first().cloned().unwrap();
 
         while frames != self.positions.len() {
             if frames > self.positions.len() {
                 self.positions.push(cloned_first_position);
+                self.scales.push(cloned_first_scale);
                 self.rotations.push(cloned_first_rotation);
+                self.flips.push(cloned_first_flip);
+                self.colors.push(cloned_first_color);
+                self.keys.push(None);
+                self.layers.push(None);
             } else {
                 self.positions.pop();
+                self.scales.pop();
                 self.rotations.pop();
+                self.flips.pop();
+                self.colors.pop();
+                self.keys.pop();
+                self.layers.pop();
             }
         }
     }
 
-    pub fn load(
-        name: String,
+    pub fn from_track(
+        track: &DoodadTrack,
         texture: Rc<Texture<'a>>,
-        positions: Vec<sdl2::rect::Rect>,
-        rotations: Vec<f64>,
-        scale: f32
+        origin: sdl2::rect::Point,
     ) -> Self {
         let width = texture.query().width;
         let heigth = texture.query().height;
 
+        let mut positions: Vec<sdl2::rect::Rect> = Vec::new();
+        let mut scales: Vec<f32> = Vec::new();
+        let mut rotations: Vec<f64> = Vec::new();
+        let mut flips: Vec<(bool, bool)> = Vec::new();
+        let mut colors: Vec<Color> = Vec::new();
+
+        let origin = match track.parent {
+            Some(_) => sdl2::rect::Point::new(0, 0),
+            None => origin,
+        };
+
+        for frame in &track.frames {
+            positions.push(sdl2::rect::Rect::new(
+                origin.x + frame.x,
+                origin.y + frame.y,
+                width,
+                heigth,
+            ));
+            scales.push(frame.scale);
+            rotations.push(frame.rotation);
+            flips.push((frame.flip_h, frame.flip_v));
+            colors.push(Color::RGBA(
+                frame.tint[0],
+                frame.tint[1],
+                frame.tint[2],
+                frame.alpha,
+            ));
+        }
+
+        let pivot = match track.pivot {
+            Some(ref pivot) => (pivot.x, pivot.y),
+            None => (width as f32 / 2.0, heigth as f32 / 2.0),
+        };
+
         Doodad {
-            name: name,
+            name: track.name.clone(),
             texture: texture,
             source_rect: sdl2::rect::Rect::new(0, 0, width, heigth),
             positions: positions,
-
-            scale: scale,
+            pivot: pivot,
+            parent: track.parent,
+            joints: Vec::new(),
+            drift: (0.0, 0.0),
+            layer: track.layer,
+            hidden: track.hidden,
+            locked: track.locked,
+
+            scales: scales,
             rotations: rotations,
+            flips: flips,
+            colors: colors,
+            keys: track.frames.iter().map(|frame| frame.key).collect(),
+            layers: track.frames.iter().map(|frame| frame.layer).collect(),
 
             current: 0,
         }
     }
 
-    pub fn serialize(&self, origin: sdl2::rect::Point) -> String {
-        let mut temp = String::new();
+    pub fn to_track(&self, origin: sdl2::rect::Point) -> DoodadTrack {
+        let mut frames = Vec::new();
+
+        let origin = match self.parent {
+            Some(_) => sdl2::rect::Point::new(0, 0),
+            None => origin,
+        };
 
-        temp += &self.name;
-        temp += ";";
-        temp += &self.scale.to_string();
-        temp += ";";
         for i in 0..self.positions.len() {
             let temp_point = self.positions[i].top_left() - origin;
-            temp += &temp_point.x.to_string();
-            temp += ",";
-            temp += &temp_point.y.to_string();
-            temp += ",";
-            temp += &self.rotations[i].to_string();
-            temp += "/";
+
+            frames.push(FrameTransform {
+                x: temp_point.x,
+                y: temp_point.y,
+                rotation: self.rotations[i],
+                scale: self.scales[i],
+                flip_h: self.flips[i].0,
+                flip_v: self.flips[i].1,
+                alpha: self.colors[i].a,
+                tint: [self.colors[i].r, self.colors[i].g, self.colors[i].b],
+                key: self.keys[i],
+                layer: self.layers[i],
+            });
+        }
+
+        DoodadTrack {
+            name: self.name.clone(),
+            pivot: Some(Pivot {
+                x: self.pivot.0,
+                y: self.pivot.1,
+            }),
+            parent: self.parent,
+            layer: self.layer,
+            hidden: self.hidden,
+            locked: self.locked,
+            frames: frames,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_frames_amount(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    pub fn set_joints(&mut self, joints: Vec<Placement>) {
+        self.joints = joints;
+    }
+
+    //every frame is converted so the doodad stays where it is on the screen
+    pub fn attach(&mut self, parent: Option<usize>, joints: Vec<Placement>) {
+        let placements: Vec<Placement> = (0..self.positions.len())
+            .map(|frame| self.placement(frame))
+            .collect();
+
+        self.parent = parent;
+        self.joints = joints;
+        self.drift = (0.0, 0.0);
+
+        for (frame, placement) in placements.iter().enumerate() {
+            self.set_placement(frame, placement);
+        }
+    }
+
+    //where the doodad is on the screen in a frame, parents included
+    pub fn placement(&self, frame: usize) -> Placement {
+        let own = self.own_placement(frame);
+
+        match self.joint(frame) {
+            Some(joint) => hierarchy::compose(&joint, &own),
+            None => own,
+        }
+    }
+
+    //relative to the parent for attached doodads
+    pub fn own_placement(&self, frame: usize) -> Placement {
+        let frame = frame % self.positions.len();
+        let position = self.positions[frame];
+
+        match self.parent {
+            Some(_) => Placement {
+                x: position.x as f32,
+                y: position.y as f32,
+                rotation: self.rotations[frame],
+                scale: self.scales[frame],
+            },
+            None => hierarchy::from_corner(
+                position.x,
+                position.y,
+                self.center_of(frame),
+                self.rotations[frame],
+                self.scales[frame],
+            ),
+        }
+    }
+
+    fn joint(&self, frame: usize) -> Option<Placement> {
+        match self.parent {
+            Some(_) => self.joints.get(frame).cloned(),
+            None => None,
+        }
+    }
+
+    fn set_placement(&mut self, frame: usize, placement: &Placement) {
+        let local = match self.joint(frame) {
+            Some(joint) => hierarchy::decompose(&joint, placement),
+            None => *placement,
+        };
+
+        let (x, y) = match self.parent {
+            Some(_) => (local.x.round() as i32, local.y.round() as i32),
+            None => hierarchy::corner(&local, self.center_of(frame)),
+        };
+
+        self.positions[frame].x = x;
+        self.positions[frame].y = y;
+        self.rotations[frame] = local.rotation;
+        self.scales[frame] = local.scale;
+    }
+
+    //top left corner on the screen before rotating
+    fn corner_at(&self, frame: usize) -> sdl2::rect::Point {
+        match self.parent {
+            Some(_) => {
+                let (x, y) = hierarchy::corner(&self.placement(frame), self.center_of(frame));
+                sdl2::rect::Point::new(x, y)
+            }
+            None => self.positions[frame].top_left(),
+        }
+    }
+
+    //layer in the current frame
+    pub fn get_layer(&self) -> i32 {
+        self.layers[self.current].unwrap_or(self.layer)
+    }
+
+    //layer of the frames without one of their own
+    pub fn get_base_layer(&self) -> i32 {
+        self.layer
+    }
+
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    pub fn get_frame_layer(&self) -> Option<i32> {
+        self.layers[self.current]
+    }
+
+    pub fn set_frame_layer(&mut self, layer: Option<i32>) {
+        self.layers[self.current] = layer;
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    pub fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    pub fn get_keyframe(&self) -> Option<Easing> {
+        self.keys[self.current]
+    }
+
+    pub fn get_keyframes(&self) -> Vec<bool> {
+        self.keys.iter().map(|key| key.is_some()).collect()
+    }
+
+    pub fn set_keyframe(&mut self, key: Option<Easing>) {
+        self.keys[self.current] = key;
+        self.tween();
+    }
+
+    //edited is the frame of the animation, the track may be shorter than it
+    pub fn retween(&mut self, edited: usize) {
+        tween::key_edited(&mut self.keys, edited);
+        self.tween();
+    }
+
+    fn tween(&mut self) {
+        let origin = sdl2::rect::Point::new(0, 0);
+        let mut track = self.to_track(origin);
+        tween::tween(&mut track.frames);
+
+        for (i, frame) in track.frames.iter().enumerate() {
+            self.positions[i].x = frame.x;
+            self.positions[i].y = frame.y;
+            self.rotations[i] = frame.rotation;
+            self.scales[i] = frame.scale;
+        }
+    }
+
+    pub fn get_pivot(&self) -> (f32, f32) {
+        self.pivot
+    }
+
+    pub fn set_pivot(&mut self, x: f32, y: f32) {
+        self.move_pivot((x, y));
+    }
+
+    //distance comes from the screen, so scale and flips of the current frame are undone
+    pub fn change_pivot(&mut self, diff_x: i32, diff_y: i32) {
+        let (flip_h, flip_v) = self.get_flip();
+        let diff_x = diff_x as f32 / self.get_scale();
+        let diff_y = diff_y as f32 / self.get_scale();
+
+        let x = self.pivot.0 + if flip_h { -diff_x } else { diff_x };
+        let y = self.pivot.1 + if flip_v { -diff_y } else { diff_y };
+
+        self.move_pivot((x, y));
+    }
+
+    //the texture keeps its corner, attached doodads are stored by their pivot so they are
+    //placed again around the new one
+    fn move_pivot(&mut self, pivot: (f32, f32)) {
+        let corners: Vec<sdl2::rect::Point> = (0..self.positions.len())
+            .map(|frame| self.corner_at(frame))
+            .collect();
+
+        self.pivot = pivot;
+
+        if self.parent.is_some() {
+            for (frame, corner) in corners.iter().enumerate() {
+                let placement = self.placement(frame);
+                let placement = hierarchy::from_corner(
+                    corner.x,
+                    corner.y,
+                    self.center_of(frame),
+                    placement.rotation,
+                    placement.scale,
+                );
+
+                self.set_placement(frame, &placement);
+            }
+        }
+    }
+
+    //pivot stays in place while the doodad rotates around it
+    pub fn pivot_position(&self) -> sdl2::rect::Point {
+        self.pivot_position_at(self.current)
+    }
+
+    //pivot in every frame, the path the doodad takes through the animation
+    pub fn path(&self) -> Vec<sdl2::rect::Point> {
+        (0..self.positions.len())
+            .map(|frame| self.pivot_position_at(frame))
+            .collect()
+    }
+
+    //moves a frame other than the current one, for dragging points of the path
+    pub fn change_frame_position(&mut self, frame: usize, diff_x: i32, diff_y: i32) {
+        let (diff_x, diff_y) = match self.joint(frame) {
+            Some(joint) => {
+                let (x, y) = hierarchy::to_local(&joint, diff_x as f32, diff_y as f32);
+                let (x, y) = (self.drift.0 + x, self.drift.1 + y);
+
+                self.drift = (x - x.round(), y - y.round());
+                (x.round() as i32, y.round() as i32)
+            }
+            None => (diff_x, diff_y),
+        };
+
+        match self.positions.get_mut(frame) {
+            Some(position) => {
+                position.x += diff_x;
+                position.y += diff_y;
+            }
+            None => {}
+        }
+    }
+
+    fn pivot_position_at(&self, frame: usize) -> sdl2::rect::Point {
+        match self.joint(frame) {
+            Some(_) => {
+                let placement = self.placement(frame);
+                return sdl2::rect::Point::new(
+                    placement.x.round() as i32,
+                    placement.y.round() as i32,
+                );
+            }
+            None => {}
         }
 
-        temp
+        let center = self.center_at(frame);
+        let scale = self.scales[frame];
+
+        self.positions[frame].top_left()
+            + sdl2::rect::Point::new(
+                (center.x as f32 * scale) as i32,
+                (center.y as f32 * scale) as i32,
+            )
     }
 
+    fn center_at(&self, frame: usize) -> sdl2::rect::Point {
+        let (flip_h, flip_v) = self.flips[frame];
+        let width = self.source_rect.width() as f32;
+        let height = self.source_rect.height() as f32;
+
+        let x = if flip_h {
+            width - self.pivot.0
+        } else {
+            self.pivot.0
+        };
+        let y = if flip_v {
+            height - self.pivot.1
+        } else {
+            self.pivot.1
+        };
+
+        sdl2::rect::Point::new(x as i32, y as i32)
+    }
+
+    fn center_of(&self, frame: usize) -> (f32, f32) {
+        let center = self.center_at(frame);
+
+        (center.x as f32, center.y as f32)
+    }
+
+    //attached doodads follow their parents
     pub fn change_all_positions(&mut self, diff_x: i32, diff_y: i32) {
+        if self.parent.is_some() {
+            return;
+        }
+
         for position in &mut self.positions {
             if position.x > 0 || diff_x > 0 {
                 position.x += diff_x;
@@ -188,17 +645,36 @@ pub trait Fragment<'a> {
     fn change_position(&mut self, diff_x: i32, diff_y: i32);
     fn set_rotation(&mut self, rotation: f64);
     fn set_scale(&mut self, scale: f32);
+    fn set_flip(&mut self, horizontal: bool, vertical: bool);
+    fn set_color(&mut self, color: Color);
 
     fn get_texture(&self) -> &sdl2::render::Texture;
     fn get_source_rect(&self) -> sdl2::rect::Rect;
     fn get_rotation(&self) -> f64;
     fn get_scale(&self) -> f32;
+    fn get_flip(&self) -> (bool, bool);
+    fn get_color(&self) -> Color;
+    //rotation center relative to the unscaled draw position, flips already applied
+    fn get_center(&self) -> sdl2::rect::Point;
 
     fn set_frame(&mut self, frame_number: usize);
+
+    //textures are shared by the resource manager, so modulation is set right before every draw
+    fn apply_color_mod(&self, color: Color) {
+        let raw = self.get_texture().raw();
+
+        unsafe {
+            sdl2::sys::SDL_SetTextureBlendMode(raw, sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_BLEND);
+            sdl2::sys::SDL_SetTextureColorMod(raw, color.r, color.g, color.b);
+            sdl2::sys::SDL_SetTextureAlphaMod(raw, color.a);
+        }
+    }
 }
 
 impl<'a> Fragment<'a> for Spritesheet<'a> {
     fn draw_position(&self) -> sdl2::rect::Rect {
+        let frame = &self.frames[self.current];
+
         let tempx = if self.position.x != 0 {
             self.position.x
         } else {
@@ -214,10 +690,10 @@ impl<'a> Fragment<'a> for Spritesheet<'a> {
             / self.scale;
 
         sdl2::rect::Rect::new(
-            tempx as i32,
-            tempy as i32,
-            self.position.width(),
-            self.position.height(),
+            tempx as i32 + frame.offset_x,
+            tempy as i32 + frame.offset_y,
+            frame.width,
+            frame.height,
         )
     }
     fn real_position(&self) -> sdl2::rect::Rect {
@@ -238,6 +714,12 @@ impl<'a> Fragment<'a> for Spritesheet<'a> {
     fn set_scale(&mut self, scale: f32) {
         self.scale = scale;
     }
+    fn set_flip(&mut self, horizontal: bool, vertical: bool) {
+        self.flip = (horizontal, vertical);
+    }
+    fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
 
     fn get_texture(&self) -> &sdl2::render::Texture<'_> {
         &self.texture
@@ -251,6 +733,18 @@ impl<'a> Fragment<'a> for Spritesheet<'a> {
     fn get_scale(&self) -> f32 {
         self.scale
     }
+    fn get_flip(&self) -> (bool, bool) {
+        self.flip
+    }
+    fn get_color(&self) -> Color {
+        self.color
+    }
+    fn get_center(&self) -> sdl2::rect::Point {
+        sdl2::rect::Point::new(
+            self.position.width() as i32 / 2,
+            self.position.height() as i32 / 2,
+        )
+    }
 
     fn set_frame(&mut self, frame_number: usize) {
         self.current = frame_number % self.frame_count;
@@ -260,39 +754,58 @@ impl<'a> Fragment<'a> for Spritesheet<'a> {
 
 impl<'a> Fragment<'a> for Doodad<'a> {
     fn draw_position(&self) -> sdl2::rect::Rect {
-        let tempx = if self.positions[self.current].x != 0 {
-            self.positions[self.current].x
-        } else {
-            1
-        } as f32
-            / self.scale;
+        let position = self.real_position();
+        let scale = self.get_scale();
 
-        let tempy = if self.positions[self.current].y != 0 {
-            self.positions[self.current].y
-        } else {
-            1
-        } as f32
-            / self.scale;
+        let tempx = if position.x != 0 { position.x } else { 1 } as f32 / scale;
+
+        let tempy = if position.y != 0 { position.y } else { 1 } as f32 / scale;
 
         sdl2::rect::Rect::new(
             tempx as i32,
             tempy as i32,
-            self.positions[self.current].width(),
-            self.positions[self.current].height(),
+            position.width(),
+            position.height(),
         )
     }
     fn real_position(&self) -> sdl2::rect::Rect {
-        self.positions[self.current]
+        let corner = self.corner_at(self.current);
+        let position = self.positions[self.current];
+
+        sdl2::rect::Rect::new(corner.x, corner.y, position.width(), position.height())
     }
     fn change_position(&mut self, diff_x: i32, diff_y: i32) {
-        self.positions[self.current].x += diff_x;
-        self.positions[self.current].y += diff_y;
+        let frame = self.current;
+
+        self.change_frame_position(frame, diff_x, diff_y);
     }
+    //rotation and scale come from the screen, attached doodads store them relative to the parent
     fn set_rotation(&mut self, rotation: f64) {
-        self.rotations[self.current] = rotation;
+        self.rotations[self.current] = match self.joint(self.current) {
+            Some(joint) => {
+                hierarchy::decompose(
+                    &joint,
+                    &Placement {
+                        rotation: rotation,
+                        ..joint
+                    },
+                )
+                .rotation
+            }
+            None => rotation,
+        };
     }
     fn set_scale(&mut self, scale: f32) {
-        self.scale = scale;
+        self.scales[self.current] = match self.joint(self.current) {
+            Some(joint) => scale / joint.scale,
+            None => scale,
+        };
+    }
+    fn set_flip(&mut self, horizontal: bool, vertical: bool) {
+        self.flips[self.current] = (horizontal, vertical);
+    }
+    fn set_color(&mut self, color: Color) {
+        self.colors[self.current] = color;
     }
 
     fn get_texture(&self) -> &sdl2::render::Texture<'_> {
@@ -302,10 +815,19 @@ impl<'a> Fragment<'a> for Doodad<'a> {
         self.source_rect
     }
     fn get_rotation(&self) -> f64 {
-        self.rotations[self.current]
+        self.placement(self.current).rotation
     }
     fn get_scale(&self) -> f32 {
-        self.scale
+        self.placement(self.current).scale
+    }
+    fn get_flip(&self) -> (bool, bool) {
+        self.flips[self.current]
+    }
+    fn get_color(&self) -> Color {
+        self.colors[self.current]
+    }
+    fn get_center(&self) -> sdl2::rect::Point {
+        self.center_at(self.current)
     }
 
     fn set_frame(&mut self, frame_number: usize) {
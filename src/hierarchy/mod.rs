@@ -0,0 +1,249 @@
+//doodads can be attached to another doodad, their frames are then relative to that parent:
+//x and y are how far their pivot is from the pivot of the parent before the parent is rotated
+//and scaled, rotation is added to the one of the parent and scale multiplied with it
+//flips, colors and pivots stay with every doodad and are not passed on to the children
+//resolve places every doodad of a frame by walking up its chain of parents
+
+//where the pivot of a doodad ends up in one frame, with the rotation and scale it ends up with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f64,
+    pub scale: f32,
+}
+
+//pivot of a doodad that is placed by its top left corner, center is the pivot with flips applied
+pub fn from_corner(x: i32, y: i32, center: (f32, f32), rotation: f64, scale: f32) -> Placement {
+    Placement {
+        x: x as f32 + center.0 * scale,
+        y: y as f32 + center.1 * scale,
+        rotation: rotation,
+        scale: scale,
+    }
+}
+
+//top left corner before rotating, the opposite of from_corner
+pub fn corner(placement: &Placement, center: (f32, f32)) -> (i32, i32) {
+    (
+        (placement.x - center.0 * placement.scale).round() as i32,
+        (placement.y - center.1 * placement.scale).round() as i32,
+    )
+}
+
+//local is relative to parent, both are already placed
+pub fn compose(parent: &Placement, local: &Placement) -> Placement {
+    let (sin, cos) = (parent.rotation.to_radians() as f32).sin_cos();
+
+    Placement {
+        x: parent.x + (local.x * cos - local.y * sin) * parent.scale,
+        y: parent.y + (local.x * sin + local.y * cos) * parent.scale,
+        rotation: turn(parent.rotation + local.rotation),
+        scale: parent.scale * local.scale,
+    }
+}
+
+//the opposite of compose, what a doodad placed at world has to store to stay there
+pub fn decompose(parent: &Placement, world: &Placement) -> Placement {
+    let (x, y) = to_local(parent, world.x - parent.x, world.y - parent.y);
+
+    Placement {
+        x: x,
+        y: y,
+        rotation: turn(world.rotation - parent.rotation),
+        scale: world.scale / parent.scale,
+    }
+}
+
+//distance on the screen as a distance in the space of the parent
+pub fn to_local(parent: &Placement, x: f32, y: f32) -> (f32, f32) {
+    let (sin, cos) = (parent.rotation.to_radians() as f32).sin_cos();
+
+    (
+        (x * cos + y * sin) / parent.scale,
+        (y * cos - x * sin) / parent.scale,
+    )
+}
+
+//placements of every doodad in one frame, own is where a doodad without a parent is
+//or where an attached one is relative to its parent
+//parents that are missing or attached in a circle are ignored, the definition check reports those
+pub fn resolve<F>(parents: &Vec<Option<usize>>, own: F) -> Vec<Placement>
+where
+    F: Fn(usize) -> Placement,
+{
+    let mut placed = vec![None; parents.len()];
+
+    for index in 0..parents.len() {
+        place(parents, &own, &mut placed, index, 0);
+    }
+
+    placed.into_iter().map(Option::unwrap).collect()
+}
+
+fn place<F>(
+    parents: &Vec<Option<usize>>,
+    own: &F,
+    placed: &mut Vec<Option<Placement>>,
+    index: usize,
+    depth: usize,
+) -> Placement
+where
+    F: Fn(usize) -> Placement,
+{
+    match placed[index] {
+        Some(placement) => return placement,
+        None => {}
+    }
+
+    let placement = match parents[index] {
+        Some(parent) if parent < parents.len() && depth < parents.len() => {
+            compose(&place(parents, own, placed, parent, depth + 1), &own(index))
+        }
+        _ => own(index),
+    };
+
+    placed[index] = Some(placement);
+    placement
+}
+
+//true when index is attached to ancestor, directly or through other doodads
+pub fn descends(parents: &Vec<Option<usize>>, index: usize, ancestor: usize) -> bool {
+    let mut current = parents.get(index).cloned().unwrap_or(None);
+
+    for _ in 0..parents.len() {
+        match current {
+            Some(parent) if parent == ancestor => return true,
+            Some(parent) => current = parents.get(parent).cloned().unwrap_or(None),
+            None => return false,
+        }
+    }
+
+    //only a circle gets this far
+    true
+}
+
+//degrees between 0 and 360, the range the rotation slider uses
+fn turn(degrees: f64) -> f64 {
+    let degrees = degrees % 360.0;
+
+    match degrees < 0.0 {
+        true => degrees + 360.0,
+        false => degrees,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(x: f32, y: f32, rotation: f64, scale: f32) -> Placement {
+        Placement {
+            x: x,
+            y: y,
+            rotation: rotation,
+            scale: scale,
+        }
+    }
+
+    fn assert_close(a: &Placement, b: &Placement) {
+        let close = (a.x - b.x).abs() < 0.001
+            && (a.y - b.y).abs() < 0.001
+            && (a.rotation - b.rotation).abs() < 0.001
+            && (a.scale - b.scale).abs() < 0.001;
+
+        assert!(close, "{:?} is not {:?}", a, b);
+    }
+
+    #[test]
+    fn decompose_undoes_compose() {
+        let parents = vec![
+            placement(0.0, 0.0, 0.0, 1.0),
+            placement(40.0, -12.0, 0.0, 1.0),
+            placement(40.0, -12.0, 90.0, 1.0),
+            placement(-7.5, 3.0, 215.0, 2.5),
+            placement(100.0, 100.0, 350.0, 0.25),
+        ];
+        let children = vec![
+            placement(0.0, 0.0, 0.0, 1.0),
+            placement(10.0, 5.0, 30.0, 0.5),
+            placement(-3.0, 22.0, 300.0, 2.0),
+        ];
+
+        for parent in &parents {
+            for child in &children {
+                assert_close(&decompose(parent, &compose(parent, child)), child);
+            }
+        }
+    }
+
+    #[test]
+    fn compose_rotates_and_scales_the_offset() {
+        let parent = placement(10.0, 10.0, 90.0, 2.0);
+        let world = compose(&parent, &placement(5.0, 0.0, 300.0, 0.5));
+
+        assert_close(&world, &placement(10.0, 20.0, 30.0, 1.0));
+    }
+
+    #[test]
+    fn corner_undoes_from_corner() {
+        let placement = from_corner(12, -4, (8.0, 16.0), 45.0, 1.5);
+
+        assert_eq!((placement.x, placement.y), (24.0, 20.0));
+        assert_eq!(corner(&placement, (8.0, 16.0)), (12, -4));
+    }
+
+    #[test]
+    fn resolve_walks_a_chain() {
+        //listed child first so the parents have to be placed on the way
+        let parents = vec![Some(1), Some(2), None];
+        let own = vec![
+            placement(0.0, 10.0, 0.0, 1.0),
+            placement(10.0, 0.0, 90.0, 1.0),
+            placement(100.0, 50.0, 90.0, 2.0),
+        ];
+
+        let placed = resolve(&parents, |index| own[index]);
+
+        assert_close(&placed[2], &own[2]);
+        assert_close(&placed[1], &placement(100.0, 70.0, 180.0, 2.0));
+        assert_close(&placed[0], &placement(100.0, 50.0, 180.0, 2.0));
+    }
+
+    #[test]
+    fn resolve_ignores_missing_and_circular_parents() {
+        let parents = vec![Some(1), Some(0), Some(9)];
+        let own = vec![
+            placement(1.0, 0.0, 0.0, 1.0),
+            placement(2.0, 0.0, 0.0, 1.0),
+            placement(3.0, 0.0, 0.0, 1.0),
+        ];
+
+        let placed = resolve(&parents, |index| own[index]);
+
+        assert_eq!(placed.len(), 3);
+        assert_close(&placed[2], &own[2]);
+    }
+
+    #[test]
+    fn descends_through_the_chain() {
+        let parents = vec![Some(1), Some(2), None, None];
+
+        assert!(descends(&parents, 0, 1));
+        assert!(descends(&parents, 0, 2));
+        assert!(!descends(&parents, 2, 0));
+        assert!(!descends(&parents, 0, 3));
+        assert!(!descends(&parents, 3, 0));
+    }
+
+    #[test]
+    fn descends_rejects_circles() {
+        //0 and 1 are attached to each other, 2 is attached to that circle
+        let parents = vec![Some(1), Some(0), Some(0)];
+
+        assert!(descends(&parents, 0, 1));
+        assert!(descends(&parents, 1, 0));
+        assert!(descends(&parents, 2, 2));
+        assert!(!descends(&parents, 5, 0));
+    }
+}
@@ -0,0 +1,290 @@
+use definition::AnimationDefinition;
+
+//every edit keeps the scene from before and after it, undo and redo rebuild the scene from those
+//definitions are small compared to textures, so whole snapshots are simpler than inverse operations
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edit {
+    Drag,
+    Scale,
+    Rotate,
+    Flip,
+    Pivot,
+    Color,
+    Duration,
+    Keyframe,
+    Attach,
+    Layer,
+    Visibility,
+    Lock,
+    AddDoodad,
+    ClearDoodads,
+    ChangeSpritesheet,
+    AddAnimation,
+    RemoveAnimation,
+    New,
+    Load,
+    Recover,
+}
+
+impl Edit {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Edit::Drag => "move",
+            Edit::Scale => "scale",
+            Edit::Rotate => "rotation",
+            Edit::Flip => "flip",
+            Edit::Pivot => "pivot",
+            Edit::Color => "color",
+            Edit::Duration => "frame duration",
+            Edit::Keyframe => "keyframe",
+            Edit::Attach => "attach",
+            Edit::Layer => "layer order",
+            Edit::Visibility => "visibility",
+            Edit::Lock => "lock",
+            Edit::AddDoodad => "add doodad",
+            Edit::ClearDoodads => "clear doodads",
+            Edit::ChangeSpritesheet => "change spritesheet",
+            Edit::AddAnimation => "add animation",
+            Edit::RemoveAnimation => "remove animation",
+            Edit::New => "new",
+            Edit::Load => "load",
+            Edit::Recover => "recover",
+        }
+    }
+
+    //sliders report every step of a drag, those are joined until the mouse is released,
+    //so are the swaps of a row dragged through the layer list
+    fn merges(&self) -> bool {
+        match self {
+            Edit::Scale
+            | Edit::Rotate
+            | Edit::Pivot
+            | Edit::Color
+            | Edit::Duration
+            | Edit::Layer => true,
+            _ => false,
+        }
+    }
+}
+
+struct Entry {
+    edit: Edit,
+    before: AnimationDefinition,
+    after: AnimationDefinition,
+}
+
+pub struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    merging: bool,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            merging: false,
+            limit: limit,
+        }
+    }
+
+    //returns false when the edit did not change anything and was dropped
+    pub fn record(
+        &mut self,
+        edit: Edit,
+        before: AnimationDefinition,
+        after: AnimationDefinition,
+    ) -> bool {
+        if before == after {
+            return false;
+        }
+
+        self.redo.clear();
+
+        if self.merging && edit.merges() {
+            match self.undo.last_mut() {
+                Some(ref mut last) if last.edit == edit => {
+                    last.after = after;
+                    return true;
+                }
+                _ => {}
+            }
+        }
+
+        self.undo.push(Entry {
+            edit: edit,
+            before: before,
+            after: after,
+        });
+
+        if self.undo.len() > self.limit {
+            self.undo.remove(0);
+        }
+
+        self.merging = edit.merges();
+
+        true
+    }
+
+    //called once the mouse is released, next slider edit starts a new entry
+    pub fn close(&mut self) {
+        self.merging = false;
+    }
+
+    pub fn undo(&mut self) -> Option<AnimationDefinition> {
+        self.merging = false;
+
+        match self.undo.pop() {
+            Some(entry) => {
+                let before = entry.before.clone();
+                self.redo.push(entry);
+
+                Some(before)
+            }
+            None => None,
+        }
+    }
+
+    pub fn redo(&mut self) -> Option<AnimationDefinition> {
+        self.merging = false;
+
+        match self.redo.pop() {
+            Some(entry) => {
+                let after = entry.after.clone();
+                self.undo.push(entry);
+
+                Some(after)
+            }
+            None => None,
+        }
+    }
+
+    pub fn next_undo(&self) -> Option<Edit> {
+        self.undo.last().map(|entry| entry.edit)
+    }
+
+    pub fn next_redo(&self) -> Option<Edit> {
+        self.redo.last().map(|entry| entry.edit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use definition::{Clip, SpritesheetDefinition, CURRENT_VERSION};
+
+    //snapshots told apart by their frame count
+    fn scene(frames: usize) -> AnimationDefinition {
+        AnimationDefinition {
+            version: CURRENT_VERSION,
+            doodads: Vec::new(),
+            animations: vec![Clip {
+                name: "walk".to_string(),
+                spritesheet: SpritesheetDefinition {
+                    name: "walk".to_string(),
+                    frames: frames,
+                    durations: vec![100; frames],
+                    grid: None,
+                },
+                tracks: Vec::new(),
+            }],
+        }
+    }
+
+    fn frames(definition: Option<AnimationDefinition>) -> Option<usize> {
+        definition.map(|definition| definition.animations[0].spritesheet.frames)
+    }
+
+    #[test]
+    fn slider_edits_merge_until_closed() {
+        let mut history = History::new(10);
+
+        assert!(history.record(Edit::Scale, scene(1), scene(2)));
+        assert!(history.record(Edit::Scale, scene(2), scene(3)));
+
+        assert_eq!(frames(history.undo()), Some(1));
+        assert_eq!(history.next_undo(), None);
+
+        history.record(Edit::Scale, scene(1), scene(2));
+        history.close();
+        history.record(Edit::Scale, scene(2), scene(3));
+
+        assert_eq!(frames(history.undo()), Some(2));
+        assert_eq!(frames(history.undo()), Some(1));
+    }
+
+    #[test]
+    fn other_edits_do_not_merge() {
+        let mut history = History::new(10);
+
+        history.record(Edit::Flip, scene(1), scene(2));
+        history.record(Edit::Flip, scene(2), scene(3));
+        history.record(Edit::Scale, scene(3), scene(4));
+        history.record(Edit::Rotate, scene(4), scene(5));
+
+        assert_eq!(history.next_undo(), Some(Edit::Rotate));
+        assert_eq!(frames(history.undo()), Some(4));
+        assert_eq!(frames(history.undo()), Some(3));
+        assert_eq!(frames(history.undo()), Some(2));
+        assert_eq!(frames(history.undo()), Some(1));
+    }
+
+    #[test]
+    fn oldest_entry_is_dropped_at_the_limit() {
+        let mut history = History::new(2);
+
+        history.record(Edit::Drag, scene(1), scene(2));
+        history.record(Edit::Drag, scene(2), scene(3));
+        history.record(Edit::Drag, scene(3), scene(4));
+
+        assert_eq!(frames(history.undo()), Some(3));
+        assert_eq!(frames(history.undo()), Some(2));
+        assert_eq!(frames(history.undo()), None);
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut history = History::new(10);
+
+        history.record(Edit::Drag, scene(1), scene(2));
+        history.undo();
+        assert_eq!(history.next_redo(), Some(Edit::Drag));
+
+        history.record(Edit::Keyframe, scene(1), scene(3));
+
+        assert_eq!(history.next_redo(), None);
+        assert_eq!(frames(history.redo()), None);
+        assert_eq!(history.next_undo(), Some(Edit::Keyframe));
+    }
+
+    #[test]
+    fn unchanged_edit_is_dropped() {
+        let mut history = History::new(10);
+
+        assert!(!history.record(Edit::Color, scene(1), scene(1)));
+        assert_eq!(history.next_undo(), None);
+    }
+
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let mut history = History::new(10);
+
+        history.record(Edit::Drag, scene(1), scene(2));
+        history.record(Edit::Color, scene(2), scene(3));
+
+        assert_eq!(frames(history.undo()), Some(2));
+        assert_eq!(frames(history.undo()), Some(1));
+        assert_eq!(frames(history.undo()), None);
+
+        assert_eq!(history.next_redo(), Some(Edit::Drag));
+        assert_eq!(frames(history.redo()), Some(2));
+        assert_eq!(frames(history.redo()), Some(3));
+        assert_eq!(frames(history.redo()), None);
+
+        assert_eq!(history.next_undo(), Some(Edit::Color));
+        assert_eq!(history.undo(), Some(scene(2)));
+    }
+}
@@ -0,0 +1,477 @@
+use sdl2::rect::{Point, Rect};
+use std::fmt;
+use std::path::Path;
+
+use atlas;
+use definition::{self, AnimationDefinition, Clip, DefinitionError, FrameTransform, Pivot};
+use file_utils;
+use mymath::rotate_rectangle;
+
+//problems that still let a definition load but make it render wrong or not at all
+//doodads and spritesheets are checked against the sizes of their pngs, nothing is drawn
+
+//same limits as the sliders of the main panel
+const SCALE_RANGE: (f32, f32) = (0.5, 6.0);
+const ROTATION_RANGE: (f64, f64) = (0.0, 360.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+//line 0 means the finding is not tied to a place in the file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub line: usize,
+    pub field: String,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(field: &str, message: String) -> Self {
+        Finding {
+            severity: Severity::Error,
+            line: 0,
+            field: field.to_string(),
+            message: message,
+        }
+    }
+
+    fn warning(field: &str, message: String) -> Self {
+        Finding {
+            severity: Severity::Warning,
+            line: 0,
+            field: field.to_string(),
+            message: message,
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {} (field: {})",
+            self.line, self.severity, self.message, self.field
+        )
+    }
+}
+
+pub fn has_errors(findings: &Vec<Finding>) -> bool {
+    findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+}
+
+pub fn lint_file(path: &Path) -> Result<Vec<Finding>, DefinitionError> {
+    let file = path.to_string_lossy().into_owned();
+
+    let data = match file_utils::load_file(path) {
+        Ok(data) => data,
+        Err(err) => return Err(DefinitionError::new(0, 0, "", err.to_string()).in_file(&file)),
+    };
+
+    lint_source(path, &data).map_err(|err| err.in_file(&file))
+}
+
+//definitions that do not parse are returned as an error, everything else as findings
+pub fn lint_source(path: &Path, data: &str) -> Result<Vec<Finding>, DefinitionError> {
+    let definition = definition::parse_source(path, data)?;
+    let lines = definition::source_lines(path, data);
+
+    let mut findings = lint(&definition);
+
+    for finding in &mut findings {
+        finding.line = lines.find(&finding.field);
+    }
+    findings.sort_by_key(|finding| finding.line);
+
+    Ok(findings)
+}
+
+pub fn lint(definition: &AnimationDefinition) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let mut sizes = Vec::new();
+
+    for (index, doodad) in definition.doodads.iter().enumerate() {
+        let field = format!("doodads[{}]", index);
+
+        sizes.push(
+            match file_utils::image_size(&file_utils::doodad_path(&doodad.name)) {
+                Ok(size) => Some(size),
+                Err(message) => {
+                    findings.push(Finding::error(
+                        &format!("{}.name", field),
+                        format!("Doodad {} can not be loaded: {}", doodad.name, message),
+                    ));
+                    None
+                }
+            },
+        );
+
+        match doodad.pivot {
+            Some(ref pivot) if !pivot.x.is_finite() || !pivot.y.is_finite() => {
+                findings.push(Finding::error(
+                    &format!("{}.pivot", field),
+                    format!("Pivot of {} is not a number", doodad.name),
+                ));
+            }
+            _ => {}
+        }
+
+        //a duplicate moves exactly like the original in every animation
+        let duplicate = (0..index).find(|&other| {
+            definition.doodads[other] == *doodad
+                && definition
+                    .animations
+                    .iter()
+                    .all(|clip| clip.tracks.get(other) == clip.tracks.get(index))
+        });
+
+        match duplicate {
+            Some(other) => findings.push(Finding::warning(
+                &field,
+                format!("Doodad {} duplicates doodads[{}]", doodad.name, other),
+            )),
+            None => {}
+        }
+    }
+
+    for (index, clip) in definition.animations.iter().enumerate() {
+        let field = format!("animations[{}]", index);
+
+        match definition.animations[..index]
+            .iter()
+            .position(|other| other.name == clip.name)
+        {
+            Some(other) => findings.push(Finding::warning(
+                &format!("{}.name", field),
+                format!(
+                    "Animation name {} is already used by animations[{}]",
+                    clip.name, other
+                ),
+            )),
+            None => {}
+        }
+
+        lint_animation(&mut findings, &field, definition, clip, &sizes);
+    }
+
+    findings
+}
+
+fn lint_animation(
+    findings: &mut Vec<Finding>,
+    field: &str,
+    definition: &AnimationDefinition,
+    clip: &Clip,
+    sizes: &Vec<Option<(u32, u32)>>,
+) {
+    let frames = clip.spritesheet.frames;
+    let name = &clip.spritesheet.name;
+
+    let spritesheet_field = |key: &str| format!("{}.spritesheet.{}", field, key);
+
+    let frame_size = match file_utils::image_size(&file_utils::spritesheet_path(name)) {
+        Ok((width, height)) => match atlas::resolve(&clip.spritesheet) {
+            Ok(atlas) => {
+                match clip.spritesheet.grid {
+                    Some(ref grid) => match atlas::check_grid(grid, width, height, frames) {
+                        Ok(()) => {}
+                        Err(message) => {
+                            findings.push(Finding::error(&spritesheet_field("grid"), message))
+                        }
+                    },
+                    None => {}
+                }
+
+                //the editor plays as many frames as the atlas has
+                if atlas.frames.len() != frames {
+                    findings.push(Finding::error(
+                        &spritesheet_field("frames"),
+                        format!(
+                            "Spritesheet {} has {} frames in its atlas but the definition has {}",
+                            name,
+                            atlas.frames.len(),
+                            frames
+                        ),
+                    ));
+                }
+
+                Some((atlas.frame_width, atlas.frame_height))
+            }
+            Err(message) => {
+                findings.push(Finding::error(
+                    &spritesheet_field("name"),
+                    format!("Atlas of {} can not be read: {}", name, message),
+                ));
+                None
+            }
+        },
+        Err(message) => {
+            findings.push(Finding::error(
+                &spritesheet_field("name"),
+                format!("Spritesheet {} can not be loaded: {}", name, message),
+            ));
+            None
+        }
+    };
+
+    //a frame without a duration is skipped while the animation plays
+    for (index, &duration) in clip.spritesheet.durations.iter().enumerate() {
+        if duration == 0 {
+            findings.push(Finding::warning(
+                &format!("{}[{}]", spritesheet_field("durations"), index),
+                format!("Frame {} of {} has no duration", index, clip.name),
+            ));
+        }
+    }
+
+    for (index, (doodad, track)) in definition.doodads.iter().zip(&clip.tracks).enumerate() {
+        let field = format!("{}.tracks[{}]", field, index);
+
+        if track.len() != frames {
+            findings.push(Finding::error(
+                &field,
+                format!(
+                    "Doodad {} has {} frames in {} but the spritesheet has {}",
+                    doodad.name,
+                    track.len(),
+                    clip.name,
+                    frames
+                ),
+            ));
+        }
+
+        for (frame_index, frame) in track.iter().enumerate() {
+            let field = format!("{}[{}]", field, frame_index);
+
+            if !check_frame(findings, &field, frame) {
+                continue;
+            }
+
+            //frames of attached doodads are relative to their parents and not to the frame
+            match (frame_size, sizes[index], doodad.parent) {
+                (Some(frame_size), Some(size), None) => {
+                    if outside_frame(&doodad.pivot, frame, frame_size, size) {
+                        findings.push(Finding::warning(
+                            &field,
+                            format!(
+                                "Doodad {} is outside of the spritesheet frame in frame {} of {}",
+                                doodad.name, frame_index, clip.name
+                            ),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+//false when the transform is not usable for any further checks
+fn check_frame(findings: &mut Vec<Finding>, field: &str, frame: &FrameTransform) -> bool {
+    let mut usable = true;
+
+    if !frame.rotation.is_finite() {
+        findings.push(Finding::error(
+            &format!("{}.rotation", field),
+            format!("Rotation is not a number: {}", frame.rotation),
+        ));
+        usable = false;
+    } else if frame.rotation < ROTATION_RANGE.0 || frame.rotation >= ROTATION_RANGE.1 {
+        findings.push(Finding::warning(
+            &format!("{}.rotation", field),
+            format!(
+                "Rotation {} is outside of {} to {}",
+                frame.rotation, ROTATION_RANGE.0, ROTATION_RANGE.1
+            ),
+        ));
+    }
+
+    if !frame.scale.is_finite() || frame.scale <= 0.0 {
+        findings.push(Finding::error(
+            &format!("{}.scale", field),
+            format!("Scale is not a positive number: {}", frame.scale),
+        ));
+        usable = false;
+    } else if frame.scale < SCALE_RANGE.0 || frame.scale > SCALE_RANGE.1 {
+        findings.push(Finding::warning(
+            &format!("{}.scale", field),
+            format!(
+                "Scale {} is outside of {} to {}",
+                frame.scale, SCALE_RANGE.0, SCALE_RANGE.1
+            ),
+        ));
+    }
+
+    usable
+}
+
+//bounding box of the rotated doodad against the frame, offsets are relative to the frame origin
+fn outside_frame(
+    pivot: &Option<Pivot>,
+    frame: &FrameTransform,
+    frame_size: (u32, u32),
+    size: (u32, u32),
+) -> bool {
+    let (width, height) = size;
+
+    let pivot = match *pivot {
+        Some(ref pivot) => (pivot.x, pivot.y),
+        None => (width as f32 / 2.0, height as f32 / 2.0),
+    };
+    let center = Point::new(
+        if frame.flip_h {
+            width as f32 - pivot.0
+        } else {
+            pivot.0
+        } as i32,
+        if frame.flip_v {
+            height as f32 - pivot.1
+        } else {
+            pivot.1
+        } as i32,
+    );
+
+    let corners = rotate_rectangle(
+        Rect::new(frame.x, frame.y, width, height),
+        frame.rotation as f32,
+        frame.scale,
+        center,
+        (frame.flip_h, frame.flip_v),
+    );
+
+    let left = corners.iter().map(|corner| corner.x).min().unwrap();
+    let right = corners.iter().map(|corner| corner.x).max().unwrap();
+    let top = corners.iter().map(|corner| corner.y).min().unwrap();
+    let bottom = corners.iter().map(|corner| corner.y).max().unwrap();
+
+    right <= 0 || bottom <= 0 || left >= frame_size.0 as i32 || top >= frame_size.1 as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(findings: Vec<Finding>) -> Vec<(Severity, usize, String)> {
+        findings
+            .into_iter()
+            .map(|finding| (finding.severity, finding.line, finding.field))
+            .collect()
+    }
+
+    //dummy is 100x100 and sliced into two 50x100 frames, helmet is 128x128
+    const CURRENT: &'static str = r#"{
+  "version": 4,
+  "doodads": [
+    { "name": "helmet" },
+    { "name": "missing" }
+  ],
+  "animations": [
+    {
+      "name": "idle",
+      "spritesheet": { "name": "dummy", "frames": 2, "durations": [100, 0] },
+      "tracks": [
+        [
+          { "x": 0, "y": 0, "rotation": 0.0, "scale": 1.0 },
+          { "x": 500, "y": 0, "rotation": 400.0, "scale": 1.0 }
+        ],
+        [
+          { "x": 0, "y": 0, "rotation": 0.0, "scale": 1.0 },
+          { "x": 0, "y": 0, "rotation": 0.0, "scale": 1.0 }
+        ]
+      ]
+    }
+  ]
+}"#;
+
+    #[test]
+    fn findings_point_at_their_lines() {
+        let findings = lint_source(Path::new("current.json"), CURRENT).unwrap();
+
+        assert_eq!(
+            summary(findings),
+            vec![
+                (Severity::Error, 5, "doodads[1].name".to_string()),
+                (
+                    Severity::Warning,
+                    10,
+                    "animations[0].spritesheet.durations[1]".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    14,
+                    "animations[0].tracks[0][1].rotation".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    14,
+                    "animations[0].tracks[0][1]".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn migrated_findings_have_no_lines() {
+        let data = CURRENT
+            .replace("\"version\": 4", "\"version\": 3")
+            .replace("\"durations\": [100, 0]", "\"framerate\": 100");
+        let findings = lint_source(Path::new("migrated.json"), &data).unwrap();
+
+        assert_eq!(
+            summary(findings),
+            vec![
+                (Severity::Error, 0, "doodads[1].name".to_string()),
+                (
+                    Severity::Warning,
+                    0,
+                    "animations[0].tracks[0][1].rotation".to_string()
+                ),
+                (
+                    Severity::Warning,
+                    0,
+                    "animations[0].tracks[0][1]".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn legacy_findings_point_at_the_doodad_line() {
+        let findings = lint_source(
+            Path::new("legacy.csv"),
+            "dummy;1;100\n\nhelmet;0.1;0,0,0/\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            summary(findings),
+            vec![(
+                Severity::Warning,
+                3,
+                "animations[0].tracks[0][0].scale".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn broken_definitions_are_errors() {
+        let err = lint_source(Path::new("broken.json"), "{ \"version\": 4 }").unwrap_err();
+
+        assert_eq!(err.message, "missing field `doodads`");
+    }
+}
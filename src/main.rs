@@ -1,7 +1,14 @@
+extern crate gif;
 extern crate gl;
 extern crate imgui;
 extern crate imgui_opengl_renderer;
+extern crate png;
 extern crate sdl2;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_path_to_error;
 
 mod lib;
 mod fragment;
@@ -11,6 +18,14 @@ mod ui_stuff;
 mod config;
 mod resource_manager;
 mod app;
+mod definition;
+mod history;
+mod lint;
+mod scene;
+mod export;
+mod atlas;
+mod tween;
+mod hierarchy;
 
 fn main() {
     let mut app = app::App::new();
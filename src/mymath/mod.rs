@@ -82,11 +82,17 @@ pub fn rotate_point(start: Point, origin: Point, degrees: f32) -> Point {
     point
 }
 
-pub fn rotate_rectangle(active_rect: sdl2::rect::Rect, rotation: f32, scale: f32) -> [Point; 4] {
+pub fn rotate_rectangle(
+    active_rect: sdl2::rect::Rect,
+    rotation: f32,
+    scale: f32,
+    center: Point,
+    flip: (bool, bool),
+) -> [Point; 4] {
     let temp_center = active_rect.top_left()
         + Point::new(
-            (active_rect.width() as f32 * scale / 2.0) as i32,
-            (active_rect.height() as f32 * scale / 2.0) as i32,
+            (center.x as f32 * scale) as i32,
+            (center.y as f32 * scale) as i32,
         );
 
     let top_left = rotate_point(active_rect.top_left(), temp_center, rotation);
@@ -110,5 +116,11 @@ pub fn rotate_rectangle(active_rect: sdl2::rect::Rect, rotation: f32, scale: f32
         rotation,
     );
 
-    [top_left, top_right, bottom_right, bottom_left] //order is very important
+    //first corner always follows the top left of the texture, so flips mirror the order
+    match flip {
+        (false, false) => [top_left, top_right, bottom_right, bottom_left], //order is very important
+        (true, false) => [top_right, top_left, bottom_left, bottom_right],
+        (false, true) => [bottom_left, bottom_right, top_right, top_left],
+        (true, true) => [bottom_right, bottom_left, top_left, top_right],
+    }
 }
@@ -4,13 +4,14 @@ use sdl2::render::{Texture, TextureCreator};
 use std::collections::HashMap;
 use std::rc::Rc;
 
-pub struct ResourceManager<'l> {
-    loader: &'l TextureCreator<sdl2::video::WindowContext>,
+//generic over the creator so the window and offscreen surfaces can both load textures
+pub struct ResourceManager<'l, T: 'l> {
+    loader: &'l TextureCreator<T>,
     cache: HashMap<String, Rc<Texture<'l>>>,
 }
 
-impl<'l> ResourceManager<'l> {
-    pub fn new(loader: &'l TextureCreator<sdl2::video::WindowContext>) -> Self {
+impl<'l, T> ResourceManager<'l, T> {
+    pub fn new(loader: &'l TextureCreator<T>) -> Self {
         ResourceManager {
             cache: HashMap::new(),
             loader: loader,
@@ -28,11 +29,11 @@ impl<'l> ResourceManager<'l> {
         )
     }
 
-    pub fn get_spritesheet(&mut self, name: &str) -> Rc<Texture<'l>> {
-        self.load("spritesheets/".to_string() + name).unwrap()
+    pub fn get_spritesheet(&mut self, name: &str) -> Result<Rc<Texture<'l>>, String> {
+        self.load("spritesheets/".to_string() + name)
     }
 
-    pub fn get_doodad(&mut self, name: &str) -> Rc<Texture<'l>> {
-        self.load("doodads/".to_string() + name).unwrap()
+    pub fn get_doodad(&mut self, name: &str) -> Result<Rc<Texture<'l>>, String> {
+        self.load("doodads/".to_string() + name)
     }
 }
@@ -0,0 +1,329 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+use sdl2::render::{Canvas, RenderTarget};
+use std::cmp::Reverse;
+
+use atlas::{self, Atlas};
+use definition::{Animation, Grid};
+use fragment::{Doodad, Fragment, Spritesheet};
+use hierarchy::{self, Placement};
+use resource_manager::ResourceManager;
+
+//conversions between definitions and fragments, shared by the editor and the exporters
+
+pub fn capture(
+    name: &str,
+    spritesheet: &Spritesheet,
+    doodads: &Vec<Doodad>,
+    durations: &Vec<u32>,
+) -> Animation {
+    let origin = spritesheet.real_position().top_left();
+
+    Animation {
+        name: name.to_string(),
+        spritesheet: spritesheet.to_definition(durations.clone()),
+        doodads: doodads
+            .iter()
+            .map(|doodad| doodad.to_track(origin))
+            .collect(),
+    }
+}
+
+//grids are cut as given, sheets with an atlas next to them are sliced by it
+//and the rest into frame_count equal frames
+pub fn spritesheet<'l, T>(
+    manager: &mut ResourceManager<'l, T>,
+    name: &str,
+    frame_count: usize,
+    grid: Option<Grid>,
+    x_pos: i32,
+    y_pos: i32,
+) -> Result<Spritesheet<'l>, String> {
+    let texture = manager.get_spritesheet(&(name.to_string() + ".png"))?;
+
+    let atlas = match grid {
+        Some(ref grid) => Some(Atlas::grid(grid, frame_count)?),
+        None => atlas::load(name)?,
+    };
+
+    match atlas {
+        Some(atlas) => Ok(Spritesheet::from_atlas(
+            name.to_string(),
+            texture,
+            x_pos,
+            y_pos,
+            atlas,
+            grid,
+        )),
+        None => Ok(Spritesheet::new(
+            name.to_string(),
+            texture,
+            x_pos,
+            y_pos,
+            frame_count,
+        )),
+    }
+}
+
+//textures are resolved up front so a broken definition leaves the scene intact
+pub fn build<'l, T>(
+    manager: &mut ResourceManager<'l, T>,
+    definition: &Animation,
+    x_pos: i32,
+    y_pos: i32,
+) -> Result<(Spritesheet<'l>, Vec<Doodad<'l>>), String> {
+    let spritesheet = spritesheet(
+        manager,
+        &definition.spritesheet.name,
+        definition.spritesheet.frames,
+        definition.spritesheet.grid,
+        x_pos,
+        y_pos,
+    )?;
+
+    let mut doodads = Vec::new();
+    for track in &definition.doodads {
+        let texture = manager.get_doodad(&(track.name.clone() + ".png"))?;
+
+        doodads.push(Doodad::from_track(track, texture, Point::new(x_pos, y_pos)));
+    }
+    place(&mut doodads);
+
+    Ok((spritesheet, doodads))
+}
+
+//attached doodads are told where their parents are in every frame, needed again whenever
+//a parent was edited and before the doodads are drawn or clicked
+pub fn place(doodads: &mut Vec<Doodad>) {
+    let parents = parents(doodads);
+    let frames = doodads
+        .iter()
+        .map(|doodad| doodad.get_frames_amount())
+        .max()
+        .unwrap_or(0);
+
+    let mut joints: Vec<Vec<Placement>> = vec![Vec::new(); doodads.len()];
+
+    for frame in 0..frames {
+        let placements = hierarchy::resolve(&parents, |index| doodads[index].own_placement(frame));
+
+        for (index, parent) in parents.iter().enumerate() {
+            match *parent {
+                Some(parent) if parent < placements.len() => joints[index].push(placements[parent]),
+                _ => {}
+            }
+        }
+    }
+
+    for (doodad, joints) in doodads.iter_mut().zip(joints) {
+        doodad.set_joints(joints);
+    }
+}
+
+//the doodad stays where it is, only its frames are stored relative to the new parent
+pub fn set_parent(
+    doodads: &mut Vec<Doodad>,
+    index: usize,
+    parent: Option<usize>,
+) -> Result<(), String> {
+    match parent {
+        Some(parent) if parent >= doodads.len() => {
+            return Err(format!("There is no doodad {} to attach to", parent));
+        }
+        Some(parent)
+            if parent == index || hierarchy::descends(&parents(doodads), parent, index) =>
+        {
+            return Err(format!(
+                "Doodad {} can not be attached to itself or to a doodad attached to it",
+                doodads[index].get_name()
+            ));
+        }
+        _ => {}
+    }
+
+    place(doodads);
+
+    let joints = match parent {
+        Some(parent) => (0..doodads[index].get_frames_amount())
+            .map(|frame| doodads[parent].placement(frame))
+            .collect(),
+        None => Vec::new(),
+    };
+    doodads[index].attach(parent, joints);
+
+    Ok(())
+}
+
+fn parents(doodads: &Vec<Doodad>) -> Vec<Option<usize>> {
+    doodads.iter().map(|doodad| doodad.get_parent()).collect()
+}
+
+//indices from the bottom to the top in the current frame, layers below 0 are behind the
+//spritesheet and doodads on the same layer keep the older rule of the first one on top
+pub fn draw_order(doodads: &Vec<Doodad>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..doodads.len()).collect();
+    order.sort_by_key(|&index| (doodads[index].get_layer(), Reverse(index)));
+
+    order
+}
+
+//order goes from the top to the bottom with none where the spritesheet is, doodads above it
+//are numbered down to layer 0 and the ones below it from layer -1
+pub fn set_layer_order(doodads: &mut Vec<Doodad>, order: &Vec<Option<usize>>) {
+    let spritesheet = order
+        .iter()
+        .position(Option::is_none)
+        .unwrap_or(order.len()) as i32;
+
+    for (row, entry) in order.iter().enumerate() {
+        match *entry {
+            Some(index) if index < doodads.len() => {
+                let row = row as i32;
+
+                doodads[index].set_layer(match row < spritesheet {
+                    true => spritesheet - 1 - row,
+                    false => spritesheet - row,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+//new doodads go on top of everything that is there
+pub fn next_layer(doodads: &Vec<Doodad>) -> i32 {
+    doodads
+        .iter()
+        .map(|doodad| doodad.get_base_layer() + 1)
+        .max()
+        .unwrap_or(0)
+        .max(0)
+}
+
+//hidden doodads are left out here and in the exports alike
+pub fn draw<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    spritesheet: &Spritesheet,
+    doodads: &Vec<Doodad>,
+) -> Result<(), String> {
+    draw_modulated(canvas, spritesheet, doodads, None)
+}
+
+//neighbouring frames faded out with distance below the current one, earlier frames tinted red
+//and later ones green, the animation is not wrapped around at its ends
+pub fn draw_onion_skin<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    spritesheet: &mut Spritesheet,
+    doodads: &mut Vec<Doodad>,
+    frame: usize,
+    neighbours: usize,
+) -> Result<(), String> {
+    let frame_count = spritesheet.get_frames_amount() as i32;
+
+    //farthest first so the nearer frames end up on top
+    for distance in (1..neighbours + 1).rev() {
+        let alpha = (150 * (neighbours + 1 - distance) / (neighbours + 1)) as u8;
+
+        for &(offset, tint) in &[
+            (-(distance as i32), Color::RGBA(255, 110, 110, alpha)),
+            (distance as i32, Color::RGBA(110, 255, 110, alpha)),
+        ] {
+            let neighbour = frame as i32 + offset;
+            if neighbour < 0 || neighbour >= frame_count {
+                continue;
+            }
+
+            set_frame(spritesheet, doodads, neighbour as usize);
+            draw_modulated(canvas, spritesheet, doodads, Some(tint))?;
+        }
+    }
+
+    set_frame(spritesheet, doodads, frame);
+
+    Ok(())
+}
+
+fn set_frame(spritesheet: &mut Spritesheet, doodads: &mut Vec<Doodad>, frame: usize) {
+    spritesheet.set_frame(frame);
+    for doodad in doodads.iter_mut() {
+        doodad.set_frame(frame);
+    }
+}
+
+//tint is multiplied into the colors of the fragments
+fn draw_modulated<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    spritesheet: &Spritesheet,
+    doodads: &Vec<Doodad>,
+    tint: Option<Color>,
+) -> Result<(), String> {
+    let modulate = |color: Color| match tint {
+        Some(tint) => Color::RGBA(
+            (color.r as u32 * tint.r as u32 / 255) as u8,
+            (color.g as u32 * tint.g as u32 / 255) as u8,
+            (color.b as u32 * tint.b as u32 / 255) as u8,
+            (color.a as u32 * tint.a as u32 / 255) as u8,
+        ),
+        None => color,
+    };
+
+    let order = draw_order(doodads);
+    let behind = order
+        .iter()
+        .take_while(|&&index| doodads[index].get_layer() < 0)
+        .count();
+
+    for &index in &order[..behind] {
+        draw_doodad(
+            canvas,
+            &doodads[index],
+            modulate(doodads[index].get_color()),
+        )?;
+    }
+
+    canvas.set_scale(spritesheet.get_scale(), spritesheet.get_scale())?;
+
+    spritesheet.apply_color_mod(modulate(spritesheet.get_color()));
+    canvas.copy_ex(
+        spritesheet.get_texture(),
+        Some(spritesheet.get_source_rect()),
+        Some(spritesheet.draw_position()),
+        spritesheet.get_rotation(),
+        None,
+        spritesheet.get_flip().0,
+        spritesheet.get_flip().1,
+    )?;
+
+    for &index in &order[behind..] {
+        draw_doodad(
+            canvas,
+            &doodads[index],
+            modulate(doodads[index].get_color()),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn draw_doodad<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    doodad: &Doodad,
+    color: Color,
+) -> Result<(), String> {
+    if doodad.is_hidden() {
+        return Ok(());
+    }
+
+    canvas.set_scale(doodad.get_scale(), doodad.get_scale())?;
+
+    doodad.apply_color_mod(color);
+    canvas.copy_ex(
+        doodad.get_texture(),
+        Some(doodad.get_source_rect()),
+        Some(doodad.draw_position()),
+        doodad.get_rotation(),
+        Some(doodad.get_center()),
+        doodad.get_flip().0,
+        doodad.get_flip().1,
+    )
+}
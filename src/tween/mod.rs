@@ -0,0 +1,258 @@
+use definition::{Easing, FrameTransform};
+
+//frames between two keyframes of a track are worked out from the keyframes around them,
+//frames before the first and after the last keyframe are left as they are
+//only position, rotation and scale are tweened, the rest stays per frame
+
+pub fn tween(frames: &mut Vec<FrameTransform>) {
+    let keys: Vec<usize> = frames
+        .iter()
+        .enumerate()
+        .filter(|&(_, frame)| frame.key.is_some())
+        .map(|(index, _)| index)
+        .collect();
+
+    for pair in keys.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let from = frames[start].clone();
+        let to = frames[end].clone();
+        let easing = from.key.unwrap();
+
+        for index in start + 1..end {
+            let progress = ease(&easing, (index - start) as f32 / (end - start) as f32);
+            let frame = &mut frames[index];
+
+            frame.x = lerp(from.x as f32, to.x as f32, progress).round() as i32;
+            frame.y = lerp(from.y as f32, to.y as f32, progress).round() as i32;
+            frame.scale = lerp(from.scale, to.scale, progress);
+            frame.rotation = rotate(from.rotation, to.rotation, progress as f64);
+        }
+    }
+}
+
+//frames between two keyframes follow them, so an edited frame in between becomes a
+//keyframe itself with the easing of the keyframe before it
+//edited is a frame of the animation, shorter tracks wrap around like they do when playing
+pub fn key_edited(keys: &mut Vec<Option<Easing>>, edited: usize) {
+    if keys.is_empty() {
+        return;
+    }
+
+    let edited = edited % keys.len();
+    let previous = (0..edited).rev().find(|&frame| keys[frame].is_some());
+    let next = (edited + 1..keys.len()).find(|&frame| keys[frame].is_some());
+
+    match (previous, next) {
+        (Some(previous), Some(_)) if keys[edited].is_none() => {
+            keys[edited] = keys[previous];
+        }
+        _ => {}
+    }
+}
+
+//0 at the first keyframe and 1 at the next one
+pub fn ease(easing: &Easing, progress: f32) -> f32 {
+    let t = progress.max(0.0).min(1.0);
+
+    match *easing {
+        Easing::Linear => t,
+        Easing::EaseIn => t * t,
+        Easing::EaseOut => t * (2.0 - t),
+        Easing::EaseInOut => match t < 0.5 {
+            true => 2.0 * t * t,
+            false => -1.0 + (4.0 - 2.0 * t) * t,
+        },
+        Easing::Bezier { x1, y1, x2, y2 } => bezier(x1, y1, x2, y2, t),
+    }
+}
+
+fn lerp(from: f32, to: f32, progress: f32) -> f32 {
+    from + (to - from) * progress
+}
+
+//degrees, turns the short way around and stays within the 0 to 360 the sliders use
+fn rotate(from: f64, to: f64, progress: f64) -> f64 {
+    let difference = ((to - from) % 360.0 + 540.0) % 360.0 - 180.0;
+    let rotation = (from + difference * progress) % 360.0;
+
+    match rotation < 0.0 {
+        true => rotation + 360.0,
+        false => rotation,
+    }
+}
+
+//css timing curve from (0, 0) to (1, 1), the x of the curve is solved for t by bisection
+fn bezier(x1: f32, y1: f32, x2: f32, y2: f32, t: f32) -> f32 {
+    let curve = |a: f32, b: f32, s: f32| {
+        3.0 * (1.0 - s) * (1.0 - s) * s * a + 3.0 * (1.0 - s) * s * s * b + s * s * s
+    };
+
+    let mut low = 0.0;
+    let mut high = 1.0;
+    for _ in 0..30 {
+        let middle = (low + high) / 2.0;
+
+        if curve(x1, x2, middle) < t {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    curve(y1, y2, (low + high) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(x: i32, rotation: f64, scale: f32, key: Option<Easing>) -> FrameTransform {
+        FrameTransform {
+            x: x,
+            y: 0,
+            rotation: rotation,
+            scale: scale,
+            flip_h: false,
+            flip_v: false,
+            alpha: 255,
+            tint: [255, 255, 255],
+            key: key,
+            layer: None,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.001
+    }
+
+    #[test]
+    fn named_easings() {
+        let at = |easing: Easing| -> Vec<f32> {
+            [0.0, 0.25, 0.5, 1.0]
+                .iter()
+                .map(|&t| ease(&easing, t))
+                .collect()
+        };
+
+        assert_eq!(at(Easing::Linear), vec![0.0, 0.25, 0.5, 1.0]);
+        assert_eq!(at(Easing::EaseIn), vec![0.0, 0.0625, 0.25, 1.0]);
+        assert_eq!(at(Easing::EaseOut), vec![0.0, 0.4375, 0.75, 1.0]);
+        assert_eq!(at(Easing::EaseInOut), vec![0.0, 0.125, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn bezier_curves() {
+        let straight = Easing::Bezier {
+            x1: 0.0,
+            y1: 0.0,
+            x2: 1.0,
+            y2: 1.0,
+        };
+        assert!(close(ease(&straight, 0.3), 0.3));
+
+        //the css ease curve
+        let css = Easing::Bezier {
+            x1: 0.25,
+            y1: 0.1,
+            x2: 0.25,
+            y2: 1.0,
+        };
+        assert!(close(ease(&css, 0.5), 0.8024));
+        assert!(close(ease(&css, 0.0), 0.0));
+        assert!(close(ease(&css, 1.0), 1.0));
+    }
+
+    #[test]
+    fn progress_is_clamped() {
+        assert_eq!(ease(&Easing::EaseOut, -0.5), 0.0);
+        assert_eq!(ease(&Easing::EaseIn, 1.5), 1.0);
+    }
+
+    #[test]
+    fn rotation_takes_the_short_way_through_0() {
+        let mut frames = vec![
+            frame(0, 350.0, 1.0, Some(Easing::Linear)),
+            frame(0, 0.0, 1.0, None),
+            frame(0, 0.0, 1.0, None),
+            frame(0, 0.0, 1.0, None),
+            frame(0, 10.0, 1.0, Some(Easing::Linear)),
+        ];
+        tween(&mut frames);
+
+        let rotations: Vec<f64> = frames.iter().map(|frame| frame.rotation).collect();
+        assert_eq!(rotations, vec![350.0, 355.0, 0.0, 5.0, 10.0]);
+
+        assert_eq!(rotate(10.0, 350.0, 0.75), 355.0);
+    }
+
+    #[test]
+    fn only_frames_between_keyframes_are_tweened() {
+        let mut frames = vec![
+            frame(7, 0.0, 3.0, None),
+            frame(0, 0.0, 1.0, Some(Easing::Linear)),
+            frame(9, 0.0, 3.0, None),
+            frame(10, 90.0, 2.0, Some(Easing::EaseIn)),
+            frame(7, 0.0, 3.0, None),
+        ];
+        tween(&mut frames);
+
+        assert_eq!(frames[0], frame(7, 0.0, 3.0, None));
+        assert_eq!(frames[1], frame(0, 0.0, 1.0, Some(Easing::Linear)));
+        assert_eq!(frames[2], frame(5, 45.0, 1.5, None));
+        assert_eq!(frames[3], frame(10, 90.0, 2.0, Some(Easing::EaseIn)));
+        assert_eq!(frames[4], frame(7, 0.0, 3.0, None));
+    }
+
+    #[test]
+    fn edited_frames_between_keyframes_become_keyframes() {
+        let mut keys = vec![Some(Easing::EaseIn), None, None, Some(Easing::Linear), None];
+
+        key_edited(&mut keys, 2);
+        key_edited(&mut keys, 4);
+
+        assert_eq!(
+            keys,
+            vec![
+                Some(Easing::EaseIn),
+                None,
+                Some(Easing::EaseIn),
+                Some(Easing::Linear),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn frames_past_a_short_track_wrap_around() {
+        //a track with 3 frames in an animation with more, frame 4 shows frame 1 of the track
+        let mut keys = vec![Some(Easing::EaseOut), None, Some(Easing::Linear)];
+
+        key_edited(&mut keys, 4);
+        key_edited(&mut keys, 7);
+
+        assert_eq!(
+            keys,
+            vec![
+                Some(Easing::EaseOut),
+                Some(Easing::EaseOut),
+                Some(Easing::Linear)
+            ]
+        );
+
+        let mut empty = Vec::new();
+        key_edited(&mut empty, 2);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn easing_of_the_earlier_keyframe_is_used() {
+        let mut frames = vec![
+            frame(0, 0.0, 1.0, Some(Easing::EaseIn)),
+            frame(0, 0.0, 1.0, None),
+            frame(100, 0.0, 1.0, Some(Easing::Linear)),
+        ];
+        tween(&mut frames);
+
+        assert_eq!(frames[1].x, 25);
+    }
+}
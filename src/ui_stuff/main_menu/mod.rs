@@ -1,13 +1,25 @@
+use atlas;
+use definition::Grid;
+use export::AnimationExport;
 use file_utils;
-use ui_stuff::{im_str, ImGuiCond, ImGuiSelectableFlags, ImString, ImVec2, Ui, UserInterface};
+use history::Edit;
+use sdl2::pixels::Color;
+use ui_stuff::{
+    im_str, popup_modal, ImGuiCond, ImGuiSelectableFlags, ImStr, ImString, ImVec2, Ui,
+    UserInterface,
+};
 
 enum WindowVisible {
     None,
-    New,
+    Confirm,
     Load,
     Save,
     AddDoodad,
     ChangeSpritesheet,
+    AddAnimation,
+    ExportSpritesheet,
+    ExportAnimation,
+    ExportEngine,
 }
 
 #[derive(Clone)]
@@ -19,7 +31,17 @@ pub enum MainMenuCommand {
     Load(String),
     Save(String),
     AddDoodad(String),
-    ChangeSpritesheet(String, u8),
+    ChangeSpritesheet(String, u8, Option<Grid>),
+    //animation name, spritesheet, frames and grid
+    AddAnimation(String, String, u8, Option<Grid>),
+    RemoveAnimation,
+    Undo,
+    Redo,
+    Recover,
+    DiscardRecovery,
+    ExportSpritesheet(String),
+    ExportAnimation(String, AnimationExport),
+    ExportEngine(String),
 }
 
 pub struct MainMenuInterface {
@@ -30,8 +52,31 @@ pub struct MainMenuInterface {
     text_input: ImString,
     frames_input: i32,
 
+    grid_enabled: bool,
+    grid_columns: i32,
+    grid_rows: i32,
+    grid_frame_width: i32,
+    grid_frame_height: i32,
+    grid_margin: i32,
+    grid_spacing: i32,
+
     selected: usize,
     list_directory: Vec<ImString>,
+
+    error: Option<String>,
+    open_error: bool,
+
+    undo: Option<Edit>,
+    redo: Option<Edit>,
+
+    dirty: bool,
+    pending: MainMenuCommand,
+    open_recovery: bool,
+
+    export_scale: f32,
+    export_transparent: bool,
+    export_background: [f32; 3],
+    export_loops: i32,
 }
 
 impl MainMenuInterface {
@@ -45,8 +90,31 @@ impl MainMenuInterface {
             text_input: ImString::with_capacity(64),
             frames_input: 0,
 
+            grid_enabled: false,
+            grid_columns: 1,
+            grid_rows: 1,
+            grid_frame_width: 0,
+            grid_frame_height: 0,
+            grid_margin: 0,
+            grid_spacing: 0,
+
             selected: 0,
             list_directory: Vec::new(),
+
+            error: None,
+            open_error: false,
+
+            undo: None,
+            redo: None,
+
+            dirty: false,
+            pending: MainMenuCommand::None,
+            open_recovery: false,
+
+            export_scale: 1.0,
+            export_transparent: false,
+            export_background: [0.0, 0.0, 0.0],
+            export_loops: 0,
         }
     }
 
@@ -60,7 +128,10 @@ impl MainMenuInterface {
             WindowVisible::AddDoodad => {
                 self.list_directory = file_utils::get_imgui_directory("./resources/doodads")
             }
-            WindowVisible::ChangeSpritesheet => {
+            WindowVisible::ChangeSpritesheet | WindowVisible::AddAnimation => {
+                self.list_directory = file_utils::get_imgui_directory("./resources/spritesheets")
+            }
+            WindowVisible::ExportSpritesheet => {
                 self.list_directory = file_utils::get_imgui_directory("./resources/spritesheets")
             }
             WindowVisible::Load | WindowVisible::Save => {
@@ -70,6 +141,263 @@ impl MainMenuInterface {
         }
 
         self.list_directory.sort();
+        self.list_directory.dedup();
+    }
+
+    fn grid_input(&self) -> Grid {
+        Grid {
+            columns: self.grid_columns.max(0) as u32,
+            rows: self.grid_rows.max(0) as u32,
+            frame_width: self.grid_frame_width.max(0) as u32,
+            frame_height: self.grid_frame_height.max(0) as u32,
+            margin: self.grid_margin.max(0) as u32,
+            spacing: self.grid_spacing.max(0) as u32,
+        }
+    }
+
+    //no part of a grid that fits can be larger than the image
+    fn clamp_grid(&mut self, width: u32, height: u32) {
+        let (width, height) = (width as i32, height as i32);
+
+        self.grid_columns = self.grid_columns.min(width);
+        self.grid_rows = self.grid_rows.min(height);
+        self.grid_frame_width = self.grid_frame_width.min(width);
+        self.grid_frame_height = self.grid_frame_height.min(height);
+        self.grid_margin = self.grid_margin.min(width.max(height));
+        self.grid_spacing = self.grid_spacing.min(width.max(height));
+    }
+
+    fn selected_spritesheet(&self) -> Option<String> {
+        self.list_directory
+            .get(self.selected)
+            .map(|name| name.to_str().to_owned())
+    }
+
+    //grid being set up in the spritesheet dialog, drawn over the texture by the app
+    pub fn slicing_preview(&self) -> Option<(String, Grid, usize)> {
+        match self.window {
+            WindowVisible::ChangeSpritesheet | WindowVisible::AddAnimation if self.grid_enabled => {
+                self.selected_spritesheet()
+                    .map(|name| (name, self.grid_input(), self.frames_input.max(0) as usize))
+            }
+            _ => None,
+        }
+    }
+
+    //spritesheet list with the frame count and the grid, returns them once button is pressed
+    fn spritesheet_picker(
+        &mut self,
+        ui: &Ui,
+        button: &ImStr,
+    ) -> Option<(String, u8, Option<Grid>)> {
+        ui.child_frame(im_str!("child frame"), (280.0, 200.0))
+            .show_borders(true)
+            .always_show_vertical_scroll_bar(true)
+            .build(|| {
+                for i in 0..self.list_directory.len() {
+                    if ui.selectable(
+                        &self.list_directory[i],
+                        i == self.selected,
+                        ImGuiSelectableFlags::empty(),
+                        ImVec2::new(0.0, 0.0),
+                    ) {
+                        self.selected = i;
+                    }
+                }
+            });
+
+        ui.separator();
+
+        ui.input_int(im_str!("frames"), &mut self.frames_input)
+            .chars_decimal(true)
+            .build();
+
+        ui.checkbox(im_str!("grid"), &mut self.grid_enabled);
+
+        //none while the grid is off or the image can not be read
+        let mut grid_problem = None;
+
+        if self.grid_enabled {
+            ui.input_int(im_str!("columns"), &mut self.grid_columns)
+                .build();
+            ui.input_int(im_str!("rows"), &mut self.grid_rows).build();
+            ui.input_int(im_str!("frame width"), &mut self.grid_frame_width)
+                .build();
+            ui.input_int(im_str!("frame height"), &mut self.grid_frame_height)
+                .build();
+            ui.input_int(im_str!("margin"), &mut self.grid_margin)
+                .build();
+            ui.input_int(im_str!("spacing"), &mut self.grid_spacing)
+                .build();
+
+            let size = self
+                .selected_spritesheet()
+                .map(|name| file_utils::image_size(&file_utils::spritesheet_path(&name)));
+
+            match size {
+                Some(Ok((width, height))) => {
+                    self.clamp_grid(width, height);
+
+                    ui.text(format!("image: {}x{}", width, height));
+
+                    if ui.button(im_str!("Fit frames"), ImVec2::new(0.0, 0.0)) {
+                        let grid = atlas::fit_grid(&self.grid_input(), width, height);
+                        self.grid_frame_width = grid.frame_width as i32;
+                        self.grid_frame_height = grid.frame_height as i32;
+                    }
+
+                    grid_problem = atlas::check_grid(
+                        &self.grid_input(),
+                        width,
+                        height,
+                        self.frames_input.max(0) as usize,
+                    )
+                    .err();
+                }
+                Some(Err(message)) => grid_problem = Some(message),
+                None => {}
+            }
+
+            match grid_problem {
+                Some(ref message) => ui.text_disabled(&ImString::new(message.as_str())),
+                None => {}
+            }
+        }
+
+        ui.separator();
+
+        if ui.button(button, ImVec2::new(0.0, 0.0)) {
+            let grid = if self.grid_enabled {
+                Some(self.grid_input())
+            } else {
+                None
+            };
+
+            if self.frames_input > 0 && self.frames_input < 127 && grid_problem.is_none() {
+                return self
+                    .selected_spritesheet()
+                    .map(|name| (name, self.frames_input as u8, grid));
+            }
+        }
+
+        None
+    }
+
+    pub fn show_error(&mut self, message: String) {
+        self.error = Some(message);
+        self.open_error = true;
+    }
+
+    fn draw_error(&mut self, ui: &Ui) {
+        if self.open_error {
+            ui.open_popup(im_str!("Error!"));
+            self.open_error = false;
+        }
+
+        let mut close = false;
+
+        popup_modal(im_str!("Error!"), || {
+            match self.error {
+                Some(ref message) => ui.text(message),
+                None => {}
+            }
+
+            ui.separator();
+
+            if ui.button(im_str!("Ok"), ImVec2::new(0.0, 0.0)) {
+                ui.close_current_popup();
+                close = true;
+            }
+        });
+
+        if close {
+            self.error = None;
+        }
+    }
+
+    pub fn set_dirty(&mut self, dirty: bool) {
+        self.dirty = dirty;
+    }
+
+    //commands that throw away the scene ask first when it has unsaved changes
+    pub fn request(&mut self, command: MainMenuCommand) {
+        if self.dirty {
+            self.pending = command;
+            self.window = WindowVisible::Confirm;
+        } else {
+            self.command = command;
+        }
+    }
+
+    pub fn set_export_background(&mut self, color: Color) {
+        self.export_background = [
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0,
+        ];
+    }
+
+    fn animation_export(&self) -> AnimationExport {
+        AnimationExport {
+            scale: self.export_scale,
+            background: if self.export_transparent {
+                None
+            } else {
+                Some(Color::RGB(
+                    (self.export_background[0] * 255.0).round() as u8,
+                    (self.export_background[1] * 255.0).round() as u8,
+                    (self.export_background[2] * 255.0).round() as u8,
+                ))
+            },
+            loops: self.export_loops as u16,
+        }
+    }
+
+    pub fn offer_recovery(&mut self) {
+        self.open_recovery = true;
+    }
+
+    fn draw_recovery(&mut self, ui: &Ui) {
+        if self.open_recovery {
+            ui.open_popup(im_str!("Recovery"));
+            self.open_recovery = false;
+        }
+
+        let mut command = MainMenuCommand::None;
+
+        popup_modal(im_str!("Recovery"), || {
+            ui.text("Last session was not closed properly.");
+            ui.text("Restore its autosave?");
+
+            ui.separator();
+
+            if ui.button(im_str!("Restore"), ImVec2::new(0.0, 0.0)) {
+                ui.close_current_popup();
+                command = MainMenuCommand::Recover;
+            }
+            ui.same_line(0.0);
+            if ui.button(im_str!("Discard"), ImVec2::new(0.0, 0.0)) {
+                ui.close_current_popup();
+                command = MainMenuCommand::DiscardRecovery;
+            }
+        });
+
+        match command {
+            MainMenuCommand::None => {}
+            command => self.command = command,
+        }
+    }
+
+    pub fn set_history(&mut self, undo: Option<Edit>, redo: Option<Edit>) {
+        self.undo = undo;
+        self.redo = redo;
+    }
+
+    fn history_label(action: &str, edit: Option<Edit>) -> ImString {
+        match edit {
+            Some(edit) => ImString::new(format!("{} {}", action, edit.name())),
+            None => ImString::new(action),
+        }
     }
 
     pub fn check(&mut self) -> MainMenuCommand {
@@ -86,8 +414,8 @@ impl UserInterface for MainMenuInterface {
         ui.main_menu_bar(|| {
             ui.menu(im_str!("File")).build(|| {
                 if ui.menu_item(im_str!("New")).build() {
-                    self.window = WindowVisible::New;
-                    self.reset();
+                    self.window = WindowVisible::None;
+                    self.request(MainMenuCommand::New);
                 }
                 if ui.menu_item(im_str!("Load")).build() {
                     self.window = WindowVisible::Load;
@@ -98,7 +426,30 @@ impl UserInterface for MainMenuInterface {
                     self.reset();
                 }
                 if ui.menu_item(im_str!("Exit")).build() {
-                    self.command = MainMenuCommand::Exit;
+                    self.window = WindowVisible::None;
+                    self.request(MainMenuCommand::Exit);
+                }
+            });
+
+            ui.menu(im_str!("Edit")).build(|| {
+                let undo_label = MainMenuInterface::history_label("Undo", self.undo);
+                let redo_label = MainMenuInterface::history_label("Redo", self.redo);
+
+                if ui
+                    .menu_item(&undo_label)
+                    .shortcut(im_str!("Ctrl+Z"))
+                    .enabled(self.undo.is_some())
+                    .build()
+                {
+                    self.command = MainMenuCommand::Undo;
+                }
+                if ui
+                    .menu_item(&redo_label)
+                    .shortcut(im_str!("Ctrl+Shift+Z"))
+                    .enabled(self.redo.is_some())
+                    .build()
+                {
+                    self.command = MainMenuCommand::Redo;
                 }
             });
 
@@ -117,21 +468,55 @@ impl UserInterface for MainMenuInterface {
                     self.reset();
                 }
             });
+
+            ui.menu(im_str!("Animations")).build(|| {
+                if ui.menu_item(im_str!("New Animation")).build() {
+                    self.window = WindowVisible::AddAnimation;
+                    self.reset();
+                }
+                if ui.menu_item(im_str!("Remove Animation")).build() {
+                    self.window = WindowVisible::None;
+                    self.command = MainMenuCommand::RemoveAnimation;
+                }
+            });
+
+            ui.menu(im_str!("Export")).build(|| {
+                if ui.menu_item(im_str!("Baked spritesheet")).build() {
+                    self.window = WindowVisible::ExportSpritesheet;
+                    self.reset();
+                }
+                if ui.menu_item(im_str!("Animation (gif, apng)")).build() {
+                    self.window = WindowVisible::ExportAnimation;
+                    self.reset();
+                }
+                if ui.menu_item(im_str!("Engine data (json)")).build() {
+                    self.window = WindowVisible::ExportEngine;
+                    self.reset();
+                }
+            });
+
+            if self.dirty {
+                ui.text_disabled(im_str!("unsaved changes"));
+            }
         });
 
         match self.window {
-            WindowVisible::New => {
+            WindowVisible::Confirm => {
                 ui.window(im_str!("Warning!"))
                     .size((300.0, 100.0), ImGuiCond::Once)
                     .position((100.0, 100.0), ImGuiCond::Once)
                     .build(|| {
-                        ui.text("No Autosave, are you sure?");
+                        ui.text("Unsaved changes will be lost, are you sure?");
 
                         ui.separator();
 
                         if ui.button(im_str!("Yes!!"), ImVec2::new(0.0, 0.0)) {
                             self.window = WindowVisible::None;
-                            self.command = MainMenuCommand::New;
+                            self.command = self.pending.clone();
+                        }
+                        ui.same_line(0.0);
+                        if ui.button(im_str!("No"), ImVec2::new(0.0, 0.0)) {
+                            self.window = WindowVisible::None;
                         }
                     });
             }
@@ -161,10 +546,10 @@ impl UserInterface for MainMenuInterface {
 
                         if ui.button(im_str!("Load!"), ImVec2::new(0.0, 0.0)) {
                             if self.selected < self.list_directory.len() {
+                                let name = self.list_directory[self.selected].to_str().to_owned();
+
                                 self.window = WindowVisible::None;
-                                self.command = MainMenuCommand::Load(
-                                    self.list_directory[self.selected].to_str().to_owned(),
-                                );
+                                self.request(MainMenuCommand::Load(name));
                             }
                         }
                     });
@@ -240,39 +625,129 @@ impl UserInterface for MainMenuInterface {
 
             WindowVisible::ChangeSpritesheet => {
                 ui.window(im_str!("SpriteSheet choose"))
+                    .size((300.0, 520.0), ImGuiCond::Once)
+                    .position((100.0, 100.0), ImGuiCond::Once)
+                    .build(|| match self.spritesheet_picker(ui, im_str!("Change!")) {
+                        Some((name, frames, grid)) => {
+                            self.window = WindowVisible::None;
+                            self.command = MainMenuCommand::ChangeSpritesheet(name, frames, grid);
+                        }
+                        None => {}
+                    });
+            }
+
+            WindowVisible::AddAnimation => {
+                ui.window(im_str!("New Animation"))
+                    .size((300.0, 550.0), ImGuiCond::Once)
+                    .position((100.0, 100.0), ImGuiCond::Once)
+                    .build(|| {
+                        ui.input_text(im_str!("name"), &mut self.text_input).build();
+
+                        ui.separator();
+
+                        match self.spritesheet_picker(ui, im_str!("Add!")) {
+                            Some((spritesheet, frames, grid)) => {
+                                if self.text_input != ImString::new("") {
+                                    self.window = WindowVisible::None;
+                                    self.command = MainMenuCommand::AddAnimation(
+                                        self.text_input.to_str().to_owned(),
+                                        spritesheet,
+                                        frames,
+                                        grid,
+                                    );
+                                }
+                            }
+                            None => {}
+                        }
+                    });
+            }
+
+            WindowVisible::ExportSpritesheet => {
+                ui.window(im_str!("Export Spritesheet"))
                     .size((300.0, 300.0), ImGuiCond::Once)
                     .position((100.0, 100.0), ImGuiCond::Once)
                     .build(|| {
+                        //names already taken, listed only so they are not picked by accident
                         ui.child_frame(im_str!("child frame"), (280.0, 200.0))
                             .show_borders(true)
                             .always_show_vertical_scroll_bar(true)
                             .build(|| {
-                                for i in 0..self.list_directory.len() {
-                                    if ui.selectable(
-                                        &self.list_directory[i],
-                                        i == self.selected,
-                                        ImGuiSelectableFlags::empty(),
-                                        ImVec2::new(0.0, 0.0),
-                                    ) {
-                                        self.selected = i;
-                                    }
+                                for name in &self.list_directory {
+                                    ui.text_disabled(name);
                                 }
                             });
 
-                        ui.separator();
+                        ui.input_text(im_str!("Filename"), &mut self.text_input)
+                            .build();
 
-                        ui.input_int(im_str!("frames"), &mut self.frames_input)
-                            .chars_decimal(true)
+                        let taken = self.list_directory.contains(&self.text_input);
+                        if taken {
+                            ui.text_disabled(im_str!("a spritesheet with this name exists"));
+                        }
+
+                        if ui.button(im_str!("Export!"), ImVec2::new(0.0, 0.0)) {
+                            if self.text_input != ImString::new("") && !taken {
+                                self.window = WindowVisible::None;
+                                self.command = MainMenuCommand::ExportSpritesheet(
+                                    self.text_input.to_str().to_owned(),
+                                );
+                            }
+                        }
+                    });
+            }
+
+            WindowVisible::ExportAnimation => {
+                ui.window(im_str!("Export Animation"))
+                    .size((300.0, 220.0), ImGuiCond::Once)
+                    .position((100.0, 100.0), ImGuiCond::Once)
+                    .build(|| {
+                        ui.input_text(im_str!("Filename"), &mut self.text_input)
+                            .build();
+
+                        ui.slider_float(im_str!("scale"), &mut self.export_scale, 0.5, 6.0)
                             .build();
 
+                        ui.checkbox(im_str!("transparent"), &mut self.export_transparent);
+                        if !self.export_transparent {
+                            ui.color_edit(im_str!("background"), &mut self.export_background)
+                                .build();
+                        }
+
+                        if ui
+                            .input_int(im_str!("loops (0 = forever)"), &mut self.export_loops)
+                            .chars_decimal(true)
+                            .build()
+                        {
+                            self.export_loops = self.export_loops.max(0).min(0xffff);
+                        }
+
                         ui.separator();
 
-                        if ui.button(im_str!("Change!"), ImVec2::new(0.0, 0.0)) {
-                            if self.frames_input > 0 && self.frames_input < 127 {
+                        if ui.button(im_str!("Export!"), ImVec2::new(0.0, 0.0)) {
+                            if self.text_input != ImString::new("") {
                                 self.window = WindowVisible::None;
-                                self.command = MainMenuCommand::ChangeSpritesheet(
-                                    self.list_directory[self.selected].to_str().to_owned(),
-                                    self.frames_input as u8,
+                                self.command = MainMenuCommand::ExportAnimation(
+                                    self.text_input.to_str().to_owned(),
+                                    self.animation_export(),
+                                );
+                            }
+                        }
+                    });
+            }
+
+            WindowVisible::ExportEngine => {
+                ui.window(im_str!("Export Engine Data"))
+                    .size((300.0, 100.0), ImGuiCond::Once)
+                    .position((100.0, 100.0), ImGuiCond::Once)
+                    .build(|| {
+                        ui.input_text(im_str!("Filename"), &mut self.text_input)
+                            .build();
+
+                        if ui.button(im_str!("Export!"), ImVec2::new(0.0, 0.0)) {
+                            if self.text_input != ImString::new("") {
+                                self.window = WindowVisible::None;
+                                self.command = MainMenuCommand::ExportEngine(
+                                    self.text_input.to_str().to_owned(),
                                 );
                             }
                         }
@@ -281,5 +756,8 @@ impl UserInterface for MainMenuInterface {
 
             _ => {}
         }
+
+        self.draw_error(ui);
+        self.draw_recovery(ui);
     }
 }
@@ -1,6 +1,11 @@
 use imgui::*;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use std::cmp::Reverse;
 pub mod timer;
 use self::timer::Timer;
+use definition::Easing;
+use hierarchy;
 
 pub mod main_menu;
 pub use self::main_menu::{MainMenuCommand, MainMenuInterface};
@@ -10,22 +15,120 @@ pub enum MainInterfaceCommand {
     None,
     Scale(f32),
     Rotate(f32),
+    Flip(bool, bool),
+    Pivot(f32, f32),
+    Color(Color),
     Frame(i32),
+    Animation(usize),
+    Duration(usize, u32),
+    UniformDuration(u32),
+    Keyframe(Option<Easing>),
+    Select(usize),
+    Attach(Option<usize>),
+    Reorder(Vec<Option<usize>>),
+    FrameLayer(Option<i32>),
+    Hide(usize, bool),
+    Lock(usize, bool),
 }
 
+//what the hierarchy and layer windows show of a doodad
+pub struct DoodadRow {
+    pub name: ImString,
+    pub parent: Option<usize>,
+    pub layer: i32,
+    //layer of the current frame when it has one of its own
+    pub frame_layer: Option<i32>,
+    pub hidden: bool,
+    pub locked: bool,
+}
+
+//how play moves through the frames, the range limits every mode when it is enabled
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Playback {
+    Loop,
+    Reverse,
+    PingPong,
+    Once,
+}
+
+//same order as the playback combo
+const PLAYBACK_MODES: [Playback; 4] = [
+    Playback::Loop,
+    Playback::Reverse,
+    Playback::PingPong,
+    Playback::Once,
+];
+
+//same order as the easing combo, bezier comes last and takes its points from the inputs
+const EASINGS: [Easing; 4] = [
+    Easing::Linear,
+    Easing::EaseIn,
+    Easing::EaseOut,
+    Easing::EaseInOut,
+];
+
+const THUMBNAIL_SIZE: (f32, f32) = (48.0, 48.0);
+const TRACK_HEIGHT: f32 = 10.0;
+
 pub trait UserInterface {
     fn draw_window(&mut self, ui: &Ui);
 }
 
+//imgui-rs does not wrap modal popups yet, open it first with ui.open_popup
+pub fn popup_modal<F: FnOnce()>(name: &ImStr, f: F) {
+    let render = unsafe {
+        sys::igBeginPopupModal(
+            name.as_ptr(),
+            std::ptr::null_mut(),
+            ImGuiWindowFlags::AlwaysAutoResize,
+        )
+    };
+
+    if render {
+        f();
+        unsafe { sys::igEndPopup() };
+    }
+}
+
 pub struct MainInterface {
     pub scale: f32,
     rotation: f32,
+    flip_h: bool,
+    flip_v: bool,
+    pivot: [f32; 2],
+    tint: [f32; 3],
+    alpha: i32,
     current_frame: i32,
     play: bool,
+    playback: Playback,
+    //ping-pong turns around at the ends of the range
+    direction: i32,
+    range_enabled: bool,
+    range: [i32; 2],
+    speed: f32,
+    onion_skin: bool,
+    onion_frames: i32,
+    motion_path: bool,
+    keyframe: bool,
+    easing: i32,
+    bezier: [f32; 4],
+
+    animations: Vec<ImString>,
+    current_animation: i32,
 
     frame_timer: Timer,
     frames_per_anim: i32,
-    framerate: i32,
+    //milliseconds, one for every frame
+    durations: Vec<u32>,
+    uniform_duration: i32,
+
+    //window area and thumbnail slots from the last frame, the app fills them in with sdl
+    timeline: Option<(Rect, Vec<Rect>)>,
+    //frames of every doodad that were moved away from its first frame, and its keyframes
+    tracks: Vec<(String, Vec<bool>, Vec<bool>)>,
+    doodads: Vec<DoodadRow>,
+    selected: usize,
+    frame_layer: i32,
 
     pub did_change: bool,
 
@@ -37,12 +140,38 @@ impl MainInterface {
         MainInterface {
             scale: 1.0,
             rotation: 0.0,
+            flip_h: false,
+            flip_v: false,
+            pivot: [0.0, 0.0],
+            tint: [1.0, 1.0, 1.0],
+            alpha: 255,
             current_frame: 0,
             play: false,
+            playback: Playback::Loop,
+            direction: 1,
+            range_enabled: false,
+            range: [0, 5],
+            speed: 1.0,
+            onion_skin: false,
+            onion_frames: 1,
+            motion_path: false,
+            keyframe: false,
+            easing: 0,
+            bezier: [0.25, 0.1, 0.25, 1.0],
+
+            animations: Vec::new(),
+            current_animation: 0,
 
             frame_timer: Timer::create(),
             frames_per_anim: 6,
-            framerate: 1000,
+            durations: Vec::new(),
+            uniform_duration: 100,
+
+            timeline: None,
+            tracks: Vec::new(),
+            doodads: Vec::new(),
+            selected: 0,
+            frame_layer: 0,
 
             did_change: false,
 
@@ -52,18 +181,90 @@ impl MainInterface {
 
     pub fn update(&mut self) {
         if self.play {
-            if self.frame_timer.did_pass(self.framerate as u64) {
-                self.current_frame = (self.current_frame + 1) % self.frames_per_anim;
+            let duration = match self.durations.get(self.current_frame as usize) {
+                Some(&duration) => (duration as f32 / self.speed) as u64,
+                None => 0,
+            };
+
+            if self.frame_timer.did_pass(duration) {
                 self.frame_timer.reset();
 
-                self.command = MainInterfaceCommand::Frame(self.current_frame);
+                match self.next_frame() {
+                    Some(frame) => {
+                        self.current_frame = frame;
+                        self.command = MainInterfaceCommand::Frame(frame);
+                    }
+                    None => self.play = false,
+                }
+            }
+        }
+    }
+
+    //first and last frame that play, both included
+    fn play_range(&self) -> (i32, i32) {
+        let last = (self.frames_per_anim - 1).max(0);
+
+        match self.range_enabled {
+            true => {
+                let first = self.range[0].max(0).min(last);
+                (first, self.range[1].max(first).min(last))
             }
+            false => (0, last),
         }
     }
 
-    pub fn change_settings(&mut self, scale: f32, rotation: f32) {
+    fn first_frame(&self) -> i32 {
+        start(self.playback, self.play_range())
+    }
+
+    //none once play once reached the end of the range
+    fn next_frame(&mut self) -> Option<i32> {
+        match step(
+            self.playback,
+            self.play_range(),
+            self.current_frame,
+            self.direction,
+        ) {
+            Some((frame, direction)) => {
+                self.direction = direction;
+                Some(frame)
+            }
+            None => None,
+        }
+    }
+
+    pub fn change_settings(
+        &mut self,
+        scale: f32,
+        rotation: f32,
+        flip: (bool, bool),
+        pivot: (f32, f32),
+        color: Color,
+    ) {
         self.scale = scale;
         self.rotation = rotation;
+        self.flip_h = flip.0;
+        self.flip_v = flip.1;
+        self.set_pivot(pivot);
+        self.tint = [
+            color.r as f32 / 255.0,
+            color.g as f32 / 255.0,
+            color.b as f32 / 255.0,
+        ];
+        self.alpha = color.a as i32;
+    }
+
+    fn color(&self) -> Color {
+        Color::RGBA(
+            (self.tint[0] * 255.0).round() as u8,
+            (self.tint[1] * 255.0).round() as u8,
+            (self.tint[2] * 255.0).round() as u8,
+            self.alpha as u8,
+        )
+    }
+
+    pub fn set_pivot(&mut self, pivot: (f32, f32)) {
+        self.pivot = [pivot.0, pivot.1];
     }
 
     pub fn reset(&mut self, frames: i32) {
@@ -71,18 +272,113 @@ impl MainInterface {
         self.play = false;
         self.current_frame = 0;
         self.frames_per_anim = frames;
+        self.range = [0, (frames - 1).max(0)];
     }
 
-    pub fn set_rotation(&mut self, rotation: f32) {
-        self.rotation = rotation;
+    pub fn set_durations(&mut self, durations: Vec<u32>) {
+        self.durations = durations;
+    }
+
+    pub fn set_duration(&mut self, frame: usize, duration: u32) {
+        match self.durations.get_mut(frame) {
+            Some(old) => *old = duration,
+            None => {}
+        }
+    }
+
+    pub fn set_uniform_duration(&mut self, duration: u32) {
+        for old in &mut self.durations {
+            *old = duration;
+        }
+    }
+
+    //frames to show on each side of the current one, hidden while playing
+    pub fn get_onion_skin(&self) -> Option<usize> {
+        match self.onion_skin && !self.play {
+            true => Some(self.onion_frames.max(1) as usize),
+            false => None,
+        }
+    }
+
+    pub fn get_motion_path(&self) -> bool {
+        self.motion_path
+    }
+
+    //the easing only follows keyed frames, so unkeying and keying again keeps the last choice
+    pub fn set_keyframe(&mut self, key: Option<Easing>) {
+        self.keyframe = key.is_some();
+
+        match key {
+            Some(Easing::Bezier { x1, y1, x2, y2 }) => {
+                self.easing = EASINGS.len() as i32;
+                self.bezier = [x1, y1, x2, y2];
+            }
+            Some(easing) => {
+                self.easing = EASINGS
+                    .iter()
+                    .position(|&known| known == easing)
+                    .unwrap_or(0) as i32;
+            }
+            None => {}
+        }
+    }
+
+    fn key(&self) -> Option<Easing> {
+        if !self.keyframe {
+            return None;
+        }
+
+        match EASINGS.get(self.easing as usize) {
+            Some(&easing) => Some(easing),
+            //the curve has to stay a function of time, so the x values are kept inside the frame
+            None => Some(Easing::Bezier {
+                x1: self.bezier[0].max(0.0).min(1.0),
+                y1: self.bezier[1],
+                x2: self.bezier[2].max(0.0).min(1.0),
+                y2: self.bezier[3],
+            }),
+        }
+    }
+
+    pub fn set_tracks(&mut self, tracks: Vec<(String, Vec<bool>, Vec<bool>)>) {
+        self.tracks = tracks;
     }
 
-    pub fn set_framerate(&mut self, framerate: i32) {
-        self.framerate = framerate;
+    pub fn set_doodads(&mut self, doodads: Vec<DoodadRow>, selected: usize) {
+        self.frame_layer = match doodads.get(selected) {
+            Some(row) => row.frame_layer.unwrap_or(row.layer),
+            None => 0,
+        };
+        self.doodads = doodads;
+        self.selected = selected;
     }
 
-    pub fn get_framerate(&self) -> i32 {
-        self.framerate
+    pub fn get_timeline(&self) -> Option<(Rect, Vec<Rect>)> {
+        self.timeline.clone()
+    }
+
+    //arrow keys, wraps around at both ends
+    pub fn step_frame(&mut self, step: i32) {
+        if self.frames_per_anim > 0 {
+            let frame = (self.current_frame + step).rem_euclid(self.frames_per_anim);
+            self.select_frame(frame);
+        }
+    }
+
+    fn select_frame(&mut self, frame: i32) {
+        self.frame_timer.reset();
+        self.play = false;
+        self.current_frame = frame;
+        self.command = MainInterfaceCommand::Frame(frame);
+    }
+
+    pub fn set_animations(&mut self, names: Vec<String>, current: usize) {
+        self.animations = names.into_iter().map(ImString::new).collect();
+        self.current_animation = current as i32;
+    }
+
+    pub fn get_durations(&self) -> &Vec<u32> {
+        &self.durations
     }
 
     pub fn check(&mut self) -> MainInterfaceCommand {
@@ -92,30 +388,458 @@ impl MainInterface {
 
         command
     }
+
+    //tree of attached doodads, clicking one selects it
+    fn draw_hierarchy(&mut self, ui: &Ui) {
+        let parents: Vec<Option<usize>> = self.doodads.iter().map(|row| row.parent).collect();
+        let active = self.selected;
+
+        ui.window(im_str!("Hierarchy"))
+            .size((220.0, 300.0), ImGuiCond::Once)
+            .position((920.0, 140.0), ImGuiCond::Once)
+            .build(|| {
+                let mut selected = None;
+
+                for (index, _) in parents.iter().enumerate().filter(|node| node.1.is_none()) {
+                    hierarchy_node(ui, &self.doodads, index, active, &mut selected);
+                }
+
+                match selected {
+                    Some(index) => self.command = MainInterfaceCommand::Select(index),
+                    None => {}
+                }
+
+                if active >= self.doodads.len() {
+                    return;
+                }
+
+                ui.separator();
+
+                //the selected doodad can not be attached to itself or to anything attached to it
+                let candidates: Vec<usize> = (0..parents.len())
+                    .filter(|&index| {
+                        index != active && !hierarchy::descends(&parents, index, active)
+                    })
+                    .collect();
+
+                let mut names: Vec<&ImStr> = vec![im_str!("nothing")];
+                names.extend(
+                    candidates
+                        .iter()
+                        .map(|&index| -> &ImStr { &self.doodads[index].name }),
+                );
+
+                let mut current = match parents[active] {
+                    Some(parent) => candidates
+                        .iter()
+                        .position(|&index| index == parent)
+                        .map_or(0, |position| position as i32 + 1),
+                    None => 0,
+                };
+
+                if ui.combo(im_str!("attached to"), &mut current, &names, 8) {
+                    self.did_change = true;
+                    self.command = MainInterfaceCommand::Attach(match current {
+                        0 => None,
+                        current => Some(candidates[current as usize - 1]),
+                    });
+                }
+            });
+    }
+
+    //doodads from the top to the bottom with the spritesheet between layer 0 and -1, a dragged
+    //row swaps places with its neighbours and the app numbers the layers again from the rows
+    fn draw_layers(&mut self, ui: &Ui) {
+        let mut rows: Vec<Option<usize>> = (0..self.doodads.len()).map(Some).collect();
+        rows.push(None);
+        rows.sort_by_key(|row| match *row {
+            Some(index) => (Reverse(self.doodads[index].layer * 2), index),
+            None => (Reverse(-1), 0),
+        });
+
+        ui.window(im_str!("Layers"))
+            .size((220.0, 260.0), ImGuiCond::Once)
+            .position((920.0, 450.0), ImGuiCond::Once)
+            .build(|| {
+                ui.text_disabled(im_str!("shown, locked, drag to reorder"));
+
+                let mut command = None;
+                let mut moved = None;
+
+                for (row, entry) in rows.iter().enumerate() {
+                    let index = match *entry {
+                        Some(index) => index,
+                        None => {
+                            ui.text_disabled(im_str!("spritesheet"));
+                            continue;
+                        }
+                    };
+                    let doodad = &self.doodads[index];
+
+                    ui.with_id(row as i32, || {
+                        let mut shown = !doodad.hidden;
+                        if ui.checkbox(im_str!("##shown"), &mut shown) {
+                            command = Some(MainInterfaceCommand::Hide(index, !shown));
+                        }
+                        ui.same_line(0.0);
+
+                        let mut locked = doodad.locked;
+                        if ui.checkbox(im_str!("##locked"), &mut locked) {
+                            command = Some(MainInterfaceCommand::Lock(index, locked));
+                        }
+                        ui.same_line(0.0);
+
+                        let label = match doodad.frame_layer {
+                            Some(layer) => ImString::new(format!(
+                                "{} (layer {} in this frame)",
+                                doodad.name.to_str(),
+                                layer
+                            )),
+                            None => doodad.name.clone(),
+                        };
+
+                        if ui.selectable(
+                            &label,
+                            index == self.selected,
+                            ImGuiSelectableFlags::empty(),
+                            (0.0, 0.0),
+                        ) {
+                            command = Some(MainInterfaceCommand::Select(index));
+                        }
+
+                        if ui.is_item_active() && !ui.is_item_hovered() {
+                            let mut delta = ImVec2::new(0.0, 0.0);
+                            unsafe { sys::igGetMouseDragDelta(&mut delta, 0, -1.0) };
+
+                            let next = match delta.y < 0.0 {
+                                true => row as i32 - 1,
+                                false => row as i32 + 1,
+                            };
+                            if next >= 0 && (next as usize) < rows.len() {
+                                moved = Some((row, next as usize));
+                                unsafe { sys::igResetMouseDragDelta(0) };
+                            }
+                        }
+                    });
+                }
+
+                match moved {
+                    Some((row, next)) => {
+                        let mut order = rows.clone();
+                        order.swap(row, next);
+                        command = Some(MainInterfaceCommand::Reorder(order));
+                    }
+                    None => {}
+                }
+
+                match self.doodads.get(self.selected) {
+                    //locked doodads can still be reordered as a whole, just not per frame
+                    Some(doodad) if !doodad.locked => {
+                        let frame_layer = doodad.frame_layer;
+
+                        ui.separator();
+
+                        let mut own = frame_layer.is_some();
+                        let toggled = ui.checkbox(im_str!("own layer in this frame"), &mut own);
+                        let changed = own
+                            && ui
+                                .input_int(im_str!("frame layer"), &mut self.frame_layer)
+                                .build();
+
+                        if toggled || changed {
+                            command = Some(MainInterfaceCommand::FrameLayer(match own {
+                                true => Some(self.frame_layer),
+                                false => None,
+                            }));
+                        }
+                    }
+                    _ => {}
+                }
+
+                match command {
+                    Some(command) => {
+                        match command {
+                            MainInterfaceCommand::Select(_) => {}
+                            _ => self.did_change = true,
+                        }
+                        self.command = command;
+                    }
+                    None => {}
+                }
+            });
+    }
+
+    fn selected_locked(&self) -> bool {
+        match self.doodads.get(self.selected) {
+            Some(row) => row.locked,
+            None => false,
+        }
+    }
+
+    //scale, rotation, flips, pivot, color and keyframe of the selected doodad
+    fn draw_transform(&mut self, ui: &Ui) {
+        if ui
+            .slider_float(im_str!("scale"), &mut self.scale, 0.5, 6.0) //needs parametrization
+            .build()
+        {
+            self.did_change = true;
+            self.command = MainInterfaceCommand::Scale(self.scale);
+        }
+
+        ui.separator();
+
+        if ui
+            .slider_float(im_str!("rotation"), &mut self.rotation, 0.0, 360.0)
+            .build()
+        {
+            self.did_change = true;
+            self.command = MainInterfaceCommand::Rotate(self.rotation);
+        }
+
+        let flip_h = ui.checkbox(im_str!("flip horizontal"), &mut self.flip_h);
+        ui.same_line(0.0);
+        let flip_v = ui.checkbox(im_str!("flip vertical"), &mut self.flip_v);
+
+        if flip_h || flip_v {
+            self.did_change = true;
+            self.command = MainInterfaceCommand::Flip(self.flip_h, self.flip_v);
+        }
+
+        if ui.input_float2(im_str!("pivot"), &mut self.pivot).build() {
+            self.did_change = true;
+            self.command = MainInterfaceCommand::Pivot(self.pivot[0], self.pivot[1]);
+        }
+
+        ui.separator();
+
+        let tint = ui.color_edit(im_str!("tint"), &mut self.tint).build();
+        let alpha = ui
+            .slider_int(im_str!("opacity"), &mut self.alpha, 0, 255)
+            .build();
+
+        if tint || alpha {
+            self.did_change = true;
+            self.command = MainInterfaceCommand::Color(self.color());
+        }
+
+        ui.separator();
+
+        let keyframe = ui.checkbox(im_str!("keyframe"), &mut self.keyframe);
+        let easing = ui.combo(
+            im_str!("easing"),
+            &mut self.easing,
+            &[
+                im_str!("linear"),
+                im_str!("ease in"),
+                im_str!("ease out"),
+                im_str!("ease in-out"),
+                im_str!("bezier"),
+            ],
+            5,
+        );
+        let bezier = self.easing == EASINGS.len() as i32
+            && ui.input_float4(im_str!("bezier"), &mut self.bezier).build();
+
+        if keyframe || (self.keyframe && (easing || bezier)) {
+            self.did_change = true;
+            self.command = MainInterfaceCommand::Keyframe(self.key());
+        }
+    }
+
+    //the window has no background so the thumbnails the app draws below imgui show through,
+    //durations are only sent as commands so the app can put them in the history
+    fn draw_timeline(&mut self, ui: &Ui) {
+        self.timeline = None;
+
+        ui.with_color_var(ImGuiCol::WindowBg, (0.0, 0.0, 0.0, 0.0), || {
+            ui.window(im_str!("Timeline"))
+                .size((800.0, 200.0), ImGuiCond::Once)
+                .position((100.0, 480.0), ImGuiCond::Once)
+                .horizontal_scrollbar(true)
+                .build(|| {
+                    ui.with_item_width(100.0, || {
+                        ui.input_int(im_str!("##uniform"), &mut self.uniform_duration)
+                            .build();
+                    });
+                    ui.same_line(0.0);
+                    if ui.button(im_str!("set all frames (ms)"), ImVec2::new(0.0, 0.0)) {
+                        self.command = MainInterfaceCommand::UniformDuration(
+                            self.uniform_duration.max(1) as u32,
+                        );
+                    }
+
+                    ui.separator();
+
+                    let mut slots = Vec::new();
+                    let mut scrubbing = false;
+                    let (first, last) = self.play_range();
+
+                    for frame in 0..self.durations.len() {
+                        if frame != 0 {
+                            ui.same_line(0.0);
+                        }
+
+                        let (x, y) = ui.get_cursor_screen_pos();
+                        slots.push(Rect::new(
+                            x as i32,
+                            y as i32,
+                            THUMBNAIL_SIZE.0 as u32,
+                            THUMBNAIL_SIZE.1 as u32,
+                        ));
+
+                        ui.with_id(frame as i32, || {
+                            ui.invisible_button(im_str!("##thumbnail"), THUMBNAIL_SIZE);
+                        });
+                        scrubbing = scrubbing || ui.is_item_active();
+
+                        if frame as i32 == self.current_frame {
+                            ui.get_window_draw_list()
+                                .add_rect(
+                                    (x - 2.0, y - 2.0),
+                                    (x + THUMBNAIL_SIZE.0 + 2.0, y + THUMBNAIL_SIZE.1 + 2.0),
+                                    [0.8, 0.1, 0.1, 1.0],
+                                )
+                                .thickness(2.0)
+                                .build();
+                        }
+
+                        if self.range_enabled && frame as i32 >= first && frame as i32 <= last {
+                            ui.get_window_draw_list()
+                                .add_line(
+                                    (x, y + THUMBNAIL_SIZE.1 + 2.0),
+                                    (x + THUMBNAIL_SIZE.0, y + THUMBNAIL_SIZE.1 + 2.0),
+                                    [0.9, 0.7, 0.1, 1.0],
+                                )
+                                .thickness(2.0)
+                                .build();
+                        }
+                    }
+
+                    //once a thumbnail is held the frame follows the mouse, even past the ends
+                    if scrubbing {
+                        let (mouse_x, _) = ui.imgui().mouse_pos();
+                        let frame = slots
+                            .iter()
+                            .rposition(|slot| slot.x() as f32 <= mouse_x)
+                            .unwrap_or(0);
+
+                        if frame as i32 != self.current_frame {
+                            self.select_frame(frame as i32);
+                        }
+                    }
+
+                    for frame in 0..self.durations.len() {
+                        if frame != 0 {
+                            ui.same_line(0.0);
+                        }
+
+                        let mut duration = self.durations[frame] as i32;
+
+                        ui.with_id(frame as i32, || {
+                            ui.with_item_width(THUMBNAIL_SIZE.0, || {
+                                if ui
+                                    .drag_int(im_str!("##duration"), &mut duration)
+                                    .min(1)
+                                    .max(60000)
+                                    .build()
+                                {
+                                    self.command = MainInterfaceCommand::Duration(
+                                        frame,
+                                        duration.max(1) as u32,
+                                    );
+                                }
+                            });
+                        });
+                    }
+
+                    if self.tracks.len() != 0 {
+                        ui.text_disabled(im_str!(
+                            "doodad frames moved away from the first one, keyframes in yellow"
+                        ));
+                    }
+
+                    let mut selected = None;
+
+                    for (row, &(ref name, ref adjusted, ref keys)) in self.tracks.iter().enumerate()
+                    {
+                        ui.with_id(&format!("track{}", row), || {
+                            for (frame, &changed) in adjusted.iter().enumerate() {
+                                if frame != 0 {
+                                    ui.same_line(0.0);
+                                }
+
+                                let (x, y) = ui.get_cursor_screen_pos();
+
+                                ui.with_id(frame as i32, || {
+                                    if ui.invisible_button(
+                                        im_str!("##cell"),
+                                        (THUMBNAIL_SIZE.0, TRACK_HEIGHT),
+                                    ) {
+                                        selected = Some(frame);
+                                    }
+                                });
+                                if ui.is_item_hovered() {
+                                    ui.tooltip_text(format!("{}, frame {}", name, frame));
+                                }
+
+                                ui.get_window_draw_list()
+                                    .add_rect(
+                                        (x, y),
+                                        (x + THUMBNAIL_SIZE.0, y + TRACK_HEIGHT),
+                                        match keys.get(frame) {
+                                            Some(&true) => [0.9, 0.8, 0.2, 1.0],
+                                            _ => [0.3, 0.7, 0.3, 1.0],
+                                        },
+                                    )
+                                    .filled(changed || keys.get(frame) == Some(&true))
+                                    .build();
+                            }
+                        });
+                    }
+
+                    match selected {
+                        Some(frame) => self.select_frame(frame as i32),
+                        None => {}
+                    }
+
+                    let mut position = ImVec2::new(0.0, 0.0);
+                    unsafe { sys::igGetWindowPos(&mut position) };
+                    let (width, height) = ui.get_window_size();
+
+                    self.timeline = Some((
+                        Rect::new(
+                            position.x as i32,
+                            position.y as i32,
+                            width as u32,
+                            height as u32,
+                        ),
+                        slots,
+                    ));
+                });
+        });
+    }
 }
 
 impl UserInterface for MainInterface {
     fn draw_window(&mut self, ui: &Ui) {
         ui.window(im_str!("Main Panel"))
-            .size((500.0, 200.0), ImGuiCond::Once)
+            .size((500.0, 420.0), ImGuiCond::Once)
             .position((400.0, 140.0), ImGuiCond::Once)
             .build(|| {
-                if ui
-                    .slider_float(im_str!("scale"), &mut self.scale, 0.5, 6.0) //needs parametrization
-                    .build()
-                {
-                    self.did_change = true;
-                    self.command = MainInterfaceCommand::Scale(self.scale);
+                let names: Vec<&ImStr> = self.animations.iter().map(|name| name.as_ref()).collect();
+
+                if ui.combo(im_str!("animation"), &mut self.current_animation, &names, 8) {
+                    self.command = MainInterfaceCommand::Animation(self.current_animation as usize);
                 }
 
                 ui.separator();
 
-                if ui
-                    .slider_float(im_str!("rotation"), &mut self.rotation, 0.0, 360.0)
-                    .build()
-                {
-                    self.did_change = true;
-                    self.command = MainInterfaceCommand::Rotate(self.rotation);
+                //the lock applies to the panel too, not only to the canvas
+                match self.selected_locked() {
+                    true => ui.text_disabled(im_str!(
+                        "locked, unlock it in the layers window to edit it"
+                    )),
+                    false => self.draw_transform(ui),
                 }
 
                 ui.separator();
@@ -135,20 +859,204 @@ impl UserInterface for MainInterface {
                     self.command = MainInterfaceCommand::Frame(self.current_frame);
                 }
 
-                if ui
-                    .input_int(im_str!("framerate"), &mut self.framerate)
-                    .chars_decimal(true)
-                    .build()
-                {
+                if ui.checkbox(im_str!("play"), &mut self.play) {
                     self.frame_timer.reset();
+                    self.direction = 1;
+                    self.current_frame = self.first_frame();
+
+                    self.command = MainInterfaceCommand::Frame(self.current_frame);
                 }
 
-                if ui.checkbox(im_str!("play"), &mut self.play) {
-                    self.frame_timer.reset();
-                    self.current_frame = 0;
+                ui.same_line(0.0);
+
+                let mut mode = self.playback as i32;
+                ui.with_item_width(120.0, || {
+                    if ui.combo(
+                        im_str!("playback"),
+                        &mut mode,
+                        &[
+                            im_str!("loop"),
+                            im_str!("reverse"),
+                            im_str!("ping-pong"),
+                            im_str!("play once"),
+                        ],
+                        4,
+                    ) {
+                        self.playback = PLAYBACK_MODES[mode as usize];
+                        self.direction = 1;
+                    }
+                });
+
+                ui.checkbox(im_str!("only range"), &mut self.range_enabled);
+                ui.same_line(0.0);
+                ui.with_item_width(120.0, || {
+                    ui.input_int2(im_str!("first, last"), &mut self.range)
+                        .build();
+                });
+
+                ui.slider_float(im_str!("speed"), &mut self.speed, 0.1, 4.0)
+                    .display_format(im_str!("%.2fx"))
+                    .build();
+
+                ui.separator();
+
+                ui.checkbox(im_str!("onion skin"), &mut self.onion_skin);
+                ui.same_line(0.0);
+                ui.with_item_width(120.0, || {
+                    ui.slider_int(im_str!("neighbours"), &mut self.onion_frames, 1, 5)
+                        .build();
+                });
+
+                ui.checkbox(im_str!("motion path"), &mut self.motion_path);
+            });
+
+        self.draw_timeline(ui);
+        self.draw_hierarchy(ui);
+        self.draw_layers(ui);
+    }
+}
+
+//frame playback starts from, range is the first and last frame that play
+fn start(playback: Playback, range: (i32, i32)) -> i32 {
+    match playback {
+        Playback::Reverse => range.1,
+        _ => range.0,
+    }
+}
+
+//frame after current together with the direction ping-pong goes in from there,
+//none once play once reached the end of the range
+fn step(playback: Playback, range: (i32, i32), current: i32, direction: i32) -> Option<(i32, i32)> {
+    let (first, last) = range;
+
+    //frames picked outside of the range while playing start it over
+    if current < first || current > last {
+        return Some((start(playback, range), direction));
+    }
+
+    match playback {
+        Playback::Loop => Some((if current == last { first } else { current + 1 }, direction)),
+        Playback::Reverse => Some((if current == first { last } else { current - 1 }, direction)),
+        Playback::PingPong => {
+            if first == last {
+                return Some((first, direction));
+            }
+
+            let next = current + direction;
+            match next < first || next > last {
+                true => Some((current - direction, -direction)),
+                false => Some((next, direction)),
+            }
+        }
+        Playback::Once => match current == last {
+            true => None,
+            false => Some((current + 1, direction)),
+        },
+    }
+}
 
-                    self.command = MainInterfaceCommand::Frame(0);
+//a doodad and everything attached to it, names can repeat so the index is the id
+fn hierarchy_node(
+    ui: &Ui,
+    doodads: &Vec<DoodadRow>,
+    index: usize,
+    active: usize,
+    selected: &mut Option<usize>,
+) {
+    let children: Vec<usize> = (0..doodads.len())
+        .filter(|&child| doodads[child].parent == Some(index))
+        .collect();
+
+    let mut opened = false;
+
+    ui.with_id(index as i32, || {
+        ui.tree_node(im_str!("##doodad"))
+            .label(&doodads[index].name)
+            .leaf(children.len() == 0)
+            .default_open(true)
+            .open_on_arrow(true)
+            .selected(index == active)
+            .build(|| {
+                opened = true;
+
+                //while open the children are drawn after the node, so it is checked first
+                if unsafe { sys::igIsItemClicked(0) } {
+                    *selected = Some(index);
+                }
+
+                for &child in &children {
+                    hierarchy_node(ui, doodads, child, active, selected);
                 }
             });
+    });
+
+    if !opened && unsafe { sys::igIsItemClicked(0) } {
+        *selected = Some(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //frames played after current, until play once stops or count frames went by
+    fn play(playback: Playback, range: (i32, i32), current: i32, count: usize) -> Vec<i32> {
+        let mut frames = Vec::new();
+        let (mut current, mut direction) = (current, 1);
+
+        while frames.len() < count {
+            match step(playback, range, current, direction) {
+                Some((frame, next)) => {
+                    frames.push(frame);
+                    current = frame;
+                    direction = next;
+                }
+                None => break,
+            }
+        }
+
+        frames
+    }
+
+    #[test]
+    fn loop_wraps_to_the_first_frame() {
+        assert_eq!(play(Playback::Loop, (0, 2), 0, 4), vec![1, 2, 0, 1]);
+        assert_eq!(play(Playback::Loop, (2, 3), 3, 3), vec![2, 3, 2]);
+    }
+
+    #[test]
+    fn reverse_wraps_to_the_last_frame() {
+        assert_eq!(start(Playback::Reverse, (1, 3)), 3);
+        assert_eq!(play(Playback::Reverse, (1, 3), 3, 4), vec![2, 1, 3, 2]);
+    }
+
+    #[test]
+    fn ping_pong_turns_at_both_ends() {
+        assert_eq!(
+            play(Playback::PingPong, (0, 2), 0, 6),
+            vec![1, 2, 1, 0, 1, 2]
+        );
+        assert_eq!(play(Playback::PingPong, (4, 5), 4, 3), vec![5, 4, 5]);
+    }
+
+    #[test]
+    fn once_stops_at_the_last_frame() {
+        assert_eq!(play(Playback::Once, (0, 2), 0, 5), vec![1, 2]);
+        assert_eq!(step(Playback::Once, (0, 2), 2, 1), None);
+    }
+
+    #[test]
+    fn single_frame_animations() {
+        assert_eq!(play(Playback::Loop, (0, 0), 0, 2), vec![0, 0]);
+        assert_eq!(play(Playback::Reverse, (0, 0), 0, 2), vec![0, 0]);
+        assert_eq!(play(Playback::PingPong, (0, 0), 0, 2), vec![0, 0]);
+        assert_eq!(play(Playback::Once, (0, 0), 0, 2), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn frames_outside_of_the_range_start_it_over() {
+        assert_eq!(step(Playback::Loop, (2, 4), 7, 1), Some((2, 1)));
+        assert_eq!(step(Playback::Reverse, (2, 4), 0, 1), Some((4, 1)));
+        assert_eq!(step(Playback::Once, (2, 4), 7, 1), Some((2, 1)));
     }
 }